@@ -0,0 +1,517 @@
+// Integer arithmetic for `$(( ))`, `(( ))` and `let`, with the operators and precedence of C
+use thiserror::Error;
+
+use crate::parser::split_subscript;
+use crate::shell::Shell;
+
+// Variables holding expressions are evaluated recursively, up to this depth
+const MAX_RECURSION: usize = 1024;
+
+#[derive(Debug, Error)]
+pub enum ArithmeticError {
+    #[error("{0}: syntax error: operand expected (error token is \"{1}\")")]
+    OperandExpected(String, String),
+    #[error("{0}: syntax error in expression (error token is \"{1}\")")]
+    Syntax(String, String),
+    #[error("{0}: division by 0 (error token is \"{1}\")")]
+    DivisionByZero(String, String),
+    #[error("{0}: exponent less than 0 (error token is \"{1}\")")]
+    NegativeExponent(String, String),
+    #[error("{0}: attempted assignment to non-variable (error token is \"{1}\")")]
+    AssignToNonVariable(String, String),
+    #[error("{0}: value too great for base (error token is \"{0}\")")]
+    BadNumber(String),
+    #[error("{0}: invalid arithmetic base (error token is \"{0}\")")]
+    BadBase(String),
+    #[error("{0}: expression recursion level exceeded (error token is \"{0}\")")]
+    Recursion(String),
+    #[error("{0}: bad array subscript")]
+    BadSubscript(String),
+    #[error("{0}: unbound variable")]
+    Unbound(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(String),
+    Name(String),
+    Op(&'static str),
+}
+
+// Operators longest first so that `<<=` wins over `<<` and `<`
+const OPERATORS: [&str; 35] = [
+    "**=", "<<=", ">>=", "**", "<<", ">>", "<=", ">=", "==", "!=", "&&", "||", "++", "--", "+=",
+    "-=", "*=", "/=", "%=", "&=", "^=", "|=", "+", "-", "*", "/", "%", "<", ">", "&", "|", "^",
+    "!", "~", "?",
+];
+
+const ASSIGNMENT_OPERATORS: [&str; 11] = [
+    "=", "+=", "-=", "*=", "/=", "%=", "<<=", ">>=", "&=", "^=", "|=",
+];
+
+#[derive(Debug)]
+enum Expr {
+    Number(i64),
+    Variable(String),
+    Unary(&'static str, Box<Expr>),
+    // `++x` and `--x`, changing the variable by the amount before using it
+    PreIncrement(String, i64),
+    // `x++` and `x--`, using the variable before changing it
+    PostIncrement(String, i64),
+    Binary(&'static str, Box<Expr>, Box<Expr>),
+    Conditional(Box<Expr>, Box<Expr>, Box<Expr>),
+    // `x = value`, or `x += value` and the like when the operator is set
+    Assign(String, Option<&'static str>, Box<Expr>),
+    Comma(Box<Expr>, Box<Expr>),
+}
+
+fn tokenize(expression: &str) -> Result<Vec<Token>, ArithmeticError> {
+    let mut tokens = Vec::new();
+    let mut rest = expression.trim_start();
+    while let Some(c) = rest.chars().next() {
+        if c.is_ascii_digit() {
+            // Numbers can contain letters for bases above 10, like `16#ff` or `0x1F`
+            let len = rest
+                .find(|c: char| !c.is_ascii_alphanumeric() && c != '#' && c != '@' && c != '_')
+                .unwrap_or(rest.len());
+            tokens.push(Token::Number(rest[..len].to_owned()));
+            rest = &rest[len..];
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            let mut len = rest
+                .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+                .unwrap_or(rest.len());
+            // An array element like `a[i + 1]`
+            if rest[len..].starts_with('[') {
+                match rest[len..].find(']') {
+                    Some(end) => len += end + 1,
+                    None => {
+                        return Err(ArithmeticError::Syntax(
+                            expression.to_owned(),
+                            rest.to_owned(),
+                        ))
+                    }
+                }
+            }
+            tokens.push(Token::Name(rest[..len].to_owned()));
+            rest = &rest[len..];
+        } else if let Some(op) = OPERATORS
+            .iter()
+            .chain(["=", "(", ")", ":", ","].iter())
+            .find(|op| rest.starts_with(**op))
+        {
+            tokens.push(Token::Op(op));
+            rest = &rest[op.len()..];
+        } else {
+            return Err(ArithmeticError::Syntax(
+                expression.to_owned(),
+                rest.trim_end().to_owned(),
+            ));
+        }
+        rest = rest.trim_start();
+    }
+    Ok(tokens)
+}
+
+// Parse a number literal: decimal, octal with a leading `0`, hexadecimal with `0x` or `base#digits`
+fn parse_number(text: &str) -> Result<i64, ArithmeticError> {
+    let bad_number = || ArithmeticError::BadNumber(text.to_owned());
+    let (base, digits) = if let Some((base, digits)) = text.split_once('#') {
+        match base.parse::<u32>() {
+            Ok(base) if (2..=64).contains(&base) => (base, digits),
+            _ => return Err(ArithmeticError::BadBase(text.to_owned())),
+        }
+    } else if let Some(digits) = text.strip_prefix("0x").or(text.strip_prefix("0X")) {
+        (16, digits)
+    } else if text.len() > 1 && text.starts_with('0') {
+        (8, &text[1..])
+    } else {
+        (10, text)
+    };
+    if digits.is_empty() {
+        return Err(bad_number());
+    }
+
+    let mut value: i64 = 0;
+    for c in digits.chars() {
+        // Digits above 9 are letters, lower case before upper case when the base is above 36
+        let digit = match c {
+            '0'..='9' => c as u32 - '0' as u32,
+            'a'..='z' => c as u32 - 'a' as u32 + 10,
+            'A'..='Z' if base <= 36 => c as u32 - 'A' as u32 + 10,
+            'A'..='Z' => c as u32 - 'A' as u32 + 36,
+            '@' => 62,
+            '_' => 63,
+            _ => return Err(bad_number()),
+        };
+        if digit >= base {
+            return Err(bad_number());
+        }
+        value = value.wrapping_mul(base as i64).wrapping_add(digit as i64);
+    }
+    Ok(value)
+}
+
+struct ExprParser<'a> {
+    expression: &'a str,
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl ExprParser<'_> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn peek_op(&self) -> Option<&'static str> {
+        match self.peek() {
+            Some(Token::Op(op)) => Some(op),
+            _ => None,
+        }
+    }
+
+    // The rest of the expression from the current token, shown in error messages
+    fn error_token(&self) -> String {
+        self.tokens[self.pos.min(self.tokens.len())..]
+            .iter()
+            .map(|token| match token {
+                Token::Number(x) | Token::Name(x) => x.as_str(),
+                Token::Op(op) => op,
+            })
+            .collect::<Vec<&str>>()
+            .join(" ")
+    }
+
+    fn syntax_error(&self) -> ArithmeticError {
+        if self.pos >= self.tokens.len() {
+            ArithmeticError::OperandExpected(self.expression.to_owned(), String::new())
+        } else {
+            ArithmeticError::Syntax(self.expression.to_owned(), self.error_token())
+        }
+    }
+
+    fn expect(&mut self, op: &str) -> Result<(), ArithmeticError> {
+        if self.peek_op() == Some(op) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            Err(self.syntax_error())
+        }
+    }
+
+    fn parse_comma(&mut self) -> Result<Expr, ArithmeticError> {
+        let mut expr = self.parse_assignment()?;
+        while self.peek_op() == Some(",") {
+            self.pos += 1;
+            expr = Expr::Comma(Box::new(expr), Box::new(self.parse_assignment()?));
+        }
+        Ok(expr)
+    }
+
+    fn parse_assignment(&mut self) -> Result<Expr, ArithmeticError> {
+        if let (Some(Token::Name(name)), Some(Token::Op(op))) =
+            (self.tokens.get(self.pos), self.tokens.get(self.pos + 1))
+        {
+            if ASSIGNMENT_OPERATORS.contains(op) {
+                let name = name.clone();
+                let op = (*op != "=").then(|| &op[..op.len() - 1]);
+                self.pos += 2;
+                let value = self.parse_assignment()?;
+                return Ok(Expr::Assign(name, op, Box::new(value)));
+            }
+        }
+
+        let expr = self.parse_conditional()?;
+        if let Some(op) = self.peek_op() {
+            if ASSIGNMENT_OPERATORS.contains(&op) {
+                return Err(ArithmeticError::AssignToNonVariable(
+                    self.expression.to_owned(),
+                    self.error_token(),
+                ));
+            }
+        }
+        Ok(expr)
+    }
+
+    fn parse_conditional(&mut self) -> Result<Expr, ArithmeticError> {
+        let condition = self.parse_binary(1)?;
+        if self.peek_op() != Some("?") {
+            return Ok(condition);
+        }
+        self.pos += 1;
+        let if_true = self.parse_comma()?;
+        self.expect(":")?;
+        let if_false = self.parse_assignment()?;
+        Ok(Expr::Conditional(
+            Box::new(condition),
+            Box::new(if_true),
+            Box::new(if_false),
+        ))
+    }
+
+    fn precedence(op: &str) -> Option<u8> {
+        Some(match op {
+            "||" => 1,
+            "&&" => 2,
+            "|" => 3,
+            "^" => 4,
+            "&" => 5,
+            "==" | "!=" => 6,
+            "<" | ">" | "<=" | ">=" => 7,
+            "<<" | ">>" => 8,
+            "+" | "-" => 9,
+            "*" | "/" | "%" => 10,
+            "**" => 11,
+            _ => return None,
+        })
+    }
+
+    // Binary operators with at least the given precedence, all left associative except `**`
+    fn parse_binary(&mut self, min_precedence: u8) -> Result<Expr, ArithmeticError> {
+        let mut left = self.parse_unary()?;
+        while let Some(op) = self.peek_op() {
+            let precedence = match ExprParser::precedence(op) {
+                Some(precedence) if precedence >= min_precedence => precedence,
+                _ => break,
+            };
+            self.pos += 1;
+            let next_precedence = if op == "**" {
+                precedence
+            } else {
+                precedence + 1
+            };
+            let right = self.parse_binary(next_precedence)?;
+            left = Expr::Binary(op, Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn parse_unary(&mut self) -> Result<Expr, ArithmeticError> {
+        match self.peek_op() {
+            Some(op @ ("-" | "+" | "!" | "~")) => {
+                self.pos += 1;
+                Ok(Expr::Unary(op, Box::new(self.parse_unary()?)))
+            }
+            Some(op @ ("++" | "--")) => {
+                self.pos += 1;
+                match self.peek() {
+                    Some(Token::Name(name)) => {
+                        let name = name.clone();
+                        self.pos += 1;
+                        Ok(Expr::PreIncrement(name, if op == "++" { 1 } else { -1 }))
+                    }
+                    // Without a variable, `--5` is just two negations
+                    _ => {
+                        let sign = if op == "++" { "+" } else { "-" };
+                        let operand = Expr::Unary(sign, Box::new(self.parse_unary()?));
+                        Ok(Expr::Unary(sign, Box::new(operand)))
+                    }
+                }
+            }
+            _ => self.parse_primary(),
+        }
+    }
+
+    fn parse_primary(&mut self) -> Result<Expr, ArithmeticError> {
+        match self.peek().cloned() {
+            Some(Token::Number(number)) => {
+                self.pos += 1;
+                Ok(Expr::Number(parse_number(&number)?))
+            }
+            Some(Token::Name(name)) => {
+                self.pos += 1;
+                match self.peek_op() {
+                    Some(op @ ("++" | "--")) => {
+                        self.pos += 1;
+                        Ok(Expr::PostIncrement(name, if op == "++" { 1 } else { -1 }))
+                    }
+                    _ => Ok(Expr::Variable(name)),
+                }
+            }
+            Some(Token::Op("(")) => {
+                self.pos += 1;
+                let expr = self.parse_comma()?;
+                self.expect(")")?;
+                Ok(expr)
+            }
+            _ => Err(self.syntax_error()),
+        }
+    }
+}
+
+impl Shell {
+    // Evaluate an arithmetic expression whose parameters have already been expanded
+    // An empty expression is 0
+    pub fn evaluate_arithmetic(&mut self, expression: &str) -> Result<i64, ArithmeticError> {
+        self.evaluate_arithmetic_at_depth(expression, 0)
+    }
+
+    fn evaluate_arithmetic_at_depth(
+        &mut self,
+        expression: &str,
+        depth: usize,
+    ) -> Result<i64, ArithmeticError> {
+        if depth > MAX_RECURSION {
+            return Err(ArithmeticError::Recursion(expression.to_owned()));
+        }
+        let tokens = tokenize(expression)?;
+        if tokens.is_empty() {
+            return Ok(0);
+        }
+
+        let mut parser = ExprParser {
+            expression,
+            tokens,
+            pos: 0,
+        };
+        let expr = parser.parse_comma()?;
+        if parser.pos < parser.tokens.len() {
+            return Err(parser.syntax_error());
+        }
+        self.evaluate_expr(&expr, expression, depth)
+    }
+
+    // Turn `name[subscript]` into `name[key]` with the key of the element, leaving other names as they are
+    fn resolve_arithmetic_name(&mut self, name: &str) -> Result<String, ArithmeticError> {
+        match split_subscript(name) {
+            Some((base, subscript)) => {
+                Ok(format!("{}[{}]", base, self.array_key(base, subscript)?))
+            }
+            None => Ok(name.to_owned()),
+        }
+    }
+
+    fn set_arithmetic_variable(&mut self, name: &str, value: i64) -> Result<(), ArithmeticError> {
+        match split_subscript(name) {
+            Some((base, subscript)) => {
+                let key = self.array_key(base, subscript)?;
+                self.variables.set_element(base, &key, &value.to_string());
+            }
+            None => self.variables.set(name, &value.to_string()),
+        }
+        Ok(())
+    }
+
+    // Value of a variable used in an expression, which can itself hold an expression
+    fn arithmetic_variable(&mut self, name: &str, depth: usize) -> Result<i64, ArithmeticError> {
+        let name = self.resolve_arithmetic_name(name)?;
+        let value = match self.get_parameter(&name) {
+            Some(value) => value,
+            None if self.option("nounset") => return Err(ArithmeticError::Unbound(name)),
+            None => String::new(),
+        };
+        let value = value.trim();
+        if value.is_empty() {
+            return Ok(0);
+        }
+        if let Ok(number) = value.parse::<i64>() {
+            return Ok(number);
+        }
+        self.evaluate_arithmetic_at_depth(value, depth + 1)
+    }
+
+    fn evaluate_expr(
+        &mut self,
+        expr: &Expr,
+        expression: &str,
+        depth: usize,
+    ) -> Result<i64, ArithmeticError> {
+        let evaluate =
+            |shell: &mut Shell, expr: &Expr| shell.evaluate_expr(expr, expression, depth);
+        Ok(match expr {
+            Expr::Number(number) => *number,
+            Expr::Variable(name) => self.arithmetic_variable(name, depth)?,
+            Expr::Unary(op, operand) => {
+                let value = evaluate(self, operand)?;
+                match *op {
+                    "-" => value.wrapping_neg(),
+                    "!" => (value == 0) as i64,
+                    "~" => !value,
+                    _ => value,
+                }
+            }
+            Expr::PreIncrement(name, amount) => {
+                let value = self.arithmetic_variable(name, depth)?.wrapping_add(*amount);
+                self.set_arithmetic_variable(name, value)?;
+                value
+            }
+            Expr::PostIncrement(name, amount) => {
+                let value = self.arithmetic_variable(name, depth)?;
+                self.set_arithmetic_variable(name, value.wrapping_add(*amount))?;
+                value
+            }
+            Expr::Binary("&&", left, right) => {
+                (evaluate(self, left)? != 0 && evaluate(self, right)? != 0) as i64
+            }
+            Expr::Binary("||", left, right) => {
+                (evaluate(self, left)? != 0 || evaluate(self, right)? != 0) as i64
+            }
+            Expr::Binary(op, left, right) => {
+                let left = evaluate(self, left)?;
+                let right = evaluate(self, right)?;
+                apply_binary(op, left, right, expression)?
+            }
+            Expr::Conditional(condition, if_true, if_false) => {
+                if evaluate(self, condition)? != 0 {
+                    evaluate(self, if_true)?
+                } else {
+                    evaluate(self, if_false)?
+                }
+            }
+            Expr::Assign(name, op, value) => {
+                let mut value = evaluate(self, value)?;
+                if let Some(op) = op {
+                    let current = self.arithmetic_variable(name, depth)?;
+                    value = apply_binary(op, current, value, expression)?;
+                }
+                self.set_arithmetic_variable(name, value)?;
+                value
+            }
+            Expr::Comma(left, right) => {
+                evaluate(self, left)?;
+                evaluate(self, right)?
+            }
+        })
+    }
+}
+
+fn apply_binary(op: &str, left: i64, right: i64, expression: &str) -> Result<i64, ArithmeticError> {
+    Ok(match op {
+        "+" => left.wrapping_add(right),
+        "-" => left.wrapping_sub(right),
+        "*" => left.wrapping_mul(right),
+        "/" | "%" => {
+            if right == 0 {
+                return Err(ArithmeticError::DivisionByZero(
+                    expression.to_owned(),
+                    right.to_string(),
+                ));
+            }
+            if op == "/" {
+                left.wrapping_div(right)
+            } else {
+                left.wrapping_rem(right)
+            }
+        }
+        "**" => {
+            if right < 0 {
+                return Err(ArithmeticError::NegativeExponent(
+                    expression.to_owned(),
+                    right.to_string(),
+                ));
+            }
+            left.wrapping_pow(right.min(u32::MAX as i64) as u32)
+        }
+        "<<" => left.wrapping_shl(right as u32),
+        ">>" => left.wrapping_shr(right as u32),
+        "<" => (left < right) as i64,
+        ">" => (left > right) as i64,
+        "<=" => (left <= right) as i64,
+        ">=" => (left >= right) as i64,
+        "==" => (left == right) as i64,
+        "!=" => (left != right) as i64,
+        "&" => left & right,
+        "^" => left ^ right,
+        "|" => left | right,
+        _ => unreachable!("unknown arithmetic operator {}", op),
+    })
+}
@@ -0,0 +1,163 @@
+// Assignments to variables and array elements, like `a=x`, `a+=(y z)` or `a[1]=w`
+use crate::arith::ArithmeticError;
+use crate::expand::ExpandError;
+use crate::lexer::{Lexer, Token};
+use crate::parser::split_subscript;
+use crate::shell::Shell;
+use crate::variables::Value;
+
+// The value of an assignment after expansion
+pub enum AssignedValue {
+    Scalar(String),
+    // The elements of `(x y [key]=z)`, with the subscript of those which have one
+    Array(Vec<(Option<String>, String)>),
+}
+
+impl AssignedValue {
+    // The value given to a command as an environment variable, where arrays can not go
+    pub fn into_scalar(self) -> String {
+        match self {
+            AssignedValue::Scalar(value) => value,
+            AssignedValue::Array(elements) => elements
+                .into_iter()
+                .map(|(_, value)| value)
+                .collect::<Vec<String>>()
+                .join(" "),
+        }
+    }
+}
+
+impl Shell {
+    // Expand the value of an assignment, which is an array when it is wrapped in parentheses
+    pub fn expand_assigned_value(&mut self, value: &str) -> Result<AssignedValue, ExpandError> {
+        let inner = match value.strip_prefix('(').and_then(|x| x.strip_suffix(')')) {
+            Some(inner) => inner,
+            None => return Ok(AssignedValue::Scalar(self.expand_word_to_string(value)?)),
+        };
+
+        let mut elements = Vec::new();
+        let mut lexer = Lexer::new(inner);
+        while let Ok((token, _, _)) = lexer.next_token() {
+            let word = match token {
+                Token::Word(word) => word,
+                Token::Newline => continue,
+                _ => break,
+            };
+            // `[subscript]=value` sets the given element, its value is never split
+            match word.strip_prefix('[').and_then(|x| x.find("]=")) {
+                Some(end) => {
+                    let subscript = self.expand_word_to_string(&word[1..end + 1])?;
+                    let value = self.expand_word_to_string(&word[end + 3..])?;
+                    elements.push((Some(subscript), value));
+                }
+                None => {
+                    for field in self.expand_word(&word)? {
+                        elements.push((None, field));
+                    }
+                }
+            }
+        }
+        Ok(AssignedValue::Array(elements))
+    }
+
+    // The key of an element of the array `name` given its expanded subscript
+    // Indexed arrays evaluate the subscript as arithmetic, with negative indices counting from the end
+    pub fn array_key(&mut self, name: &str, subscript: &str) -> Result<String, ArithmeticError> {
+        let value = self.variables.get_value(name);
+        if let Some(Value::Associative(_)) = value {
+            return Ok(subscript.to_owned());
+        }
+        let end = value.map_or(0, |value| value.next_index());
+
+        let mut index = self.evaluate_arithmetic(subscript)?;
+        if index < 0 {
+            index += end;
+            if index < 0 {
+                return Err(ArithmeticError::BadSubscript(format!(
+                    "{}[{}]",
+                    name, subscript
+                )));
+            }
+        }
+        Ok(index.to_string())
+    }
+
+    // Assign to `name` or `name[subscript]`, where `append` adds to the current value like `+=`
+    pub fn assign(
+        &mut self,
+        target: &str,
+        append: bool,
+        value: AssignedValue,
+    ) -> Result<(), ExpandError> {
+        if let Some((name, subscript)) = split_subscript(target) {
+            let value = match value {
+                AssignedValue::Scalar(value) => value,
+                AssignedValue::Array(_) => {
+                    return Err(ExpandError::ListToElement(target.to_owned()));
+                }
+            };
+            let subscript = self.expand_word_to_string(subscript)?;
+            let key = self.array_key(name, &subscript)?;
+            let value = match append {
+                true => {
+                    let current = self.variables.get_value(name);
+                    current
+                        .and_then(|x| x.element(&key))
+                        .unwrap_or("")
+                        .to_owned()
+                        + &value
+                }
+                false => value,
+            };
+            self.variables.set_element(name, &key, &value);
+            return Ok(());
+        }
+
+        let elements = match value {
+            AssignedValue::Scalar(value) => {
+                let value = match append {
+                    true => self.variables.get(target).unwrap_or("").to_owned() + &value,
+                    false => value,
+                };
+                self.variables.set(target, &value);
+                return Ok(());
+            }
+            AssignedValue::Array(elements) => elements,
+        };
+
+        let current = self.variables.get_value(target);
+        let associative = matches!(current, Some(Value::Associative(_)));
+        let mut next_index = match (append, current) {
+            (true, Some(current)) => current.next_index(),
+            _ => 0,
+        };
+        if !append {
+            let empty = match associative {
+                true => Value::Associative(Default::default()),
+                false => Value::Indexed(Default::default()),
+            };
+            self.variables.set_value(target, empty);
+        }
+
+        for (subscript, value) in elements {
+            let key = match subscript {
+                Some(subscript) => {
+                    let key = self.array_key(target, &subscript)?;
+                    if !associative {
+                        next_index = key.parse::<i64>().unwrap_or(0) + 1;
+                    }
+                    key
+                }
+                None if associative => {
+                    return Err(ExpandError::MustUseSubscript(target.to_owned()));
+                }
+                None => {
+                    next_index += 1;
+                    (next_index - 1).to_string()
+                }
+            };
+            self.variables.set_element(target, &key, &value);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,293 @@
+// The file, string and integer tests of `[[ ]]` and the `test` builtin
+use std::collections::BTreeMap;
+use std::ffi::CString;
+use std::fs::{self, Metadata};
+use std::os::unix::fs::{FileTypeExt, MetadataExt};
+
+use regex::Regex;
+
+use crate::encoding;
+use crate::parser::{ConditionalExpression, BINARY_OPERATORS, UNARY_OPERATORS};
+use crate::pattern::pattern_matches;
+use crate::shell::Shell;
+use crate::variables::Value;
+
+// Whether the file is accessible for the given mode, like `libc::R_OK`, by the effective user
+fn has_access(path: &str, mode: libc::c_int) -> bool {
+    match CString::new(encoding::encode(path)) {
+        Ok(path) => unsafe {
+            libc::faccessat(libc::AT_FDCWD, path.as_ptr(), mode, libc::AT_EACCESS) == 0
+        },
+        Err(_) => false,
+    }
+}
+
+fn modification_time(metadata: &Metadata) -> (i64, i64) {
+    (metadata.mtime(), metadata.mtime_nsec())
+}
+
+// Evaluate a test with a single operand, like `-f path` or `-z string`
+// `-v` and `-o` need the state of the shell so they are left to the callers
+pub fn unary_test(op: &str, operand: &str) -> bool {
+    let path = encoding::to_os_string(operand);
+    let metadata = fs::metadata(&path).ok();
+    let file_type = |check: fn(&Metadata) -> bool| metadata.as_ref().is_some_and(check);
+    match op {
+        "-z" => operand.is_empty(),
+        "-n" => !operand.is_empty(),
+        "-a" | "-e" => metadata.is_some(),
+        "-f" => file_type(|m| m.is_file()),
+        "-d" => file_type(|m| m.is_dir()),
+        "-b" => file_type(|m| m.file_type().is_block_device()),
+        "-c" => file_type(|m| m.file_type().is_char_device()),
+        "-p" => file_type(|m| m.file_type().is_fifo()),
+        "-S" => file_type(|m| m.file_type().is_socket()),
+        "-h" | "-L" => fs::symlink_metadata(&path).is_ok_and(|m| m.file_type().is_symlink()),
+        "-s" => file_type(|m| m.len() > 0),
+        "-u" => file_type(|m| m.mode() & 0o4000 != 0),
+        "-g" => file_type(|m| m.mode() & 0o2000 != 0),
+        "-k" => file_type(|m| m.mode() & 0o1000 != 0),
+        "-O" => file_type(|m| m.uid() == unsafe { libc::geteuid() }),
+        "-G" => file_type(|m| m.gid() == unsafe { libc::getegid() }),
+        // Modified since it was last read
+        "-N" => file_type(|m| modification_time(m) > (m.atime(), m.atime_nsec())),
+        "-r" => has_access(operand, libc::R_OK),
+        "-w" => has_access(operand, libc::W_OK),
+        "-x" => has_access(operand, libc::X_OK),
+        "-t" => operand
+            .trim()
+            .parse::<libc::c_int>()
+            .is_ok_and(|fd| unsafe { libc::isatty(fd) } == 1),
+        _ => false,
+    }
+}
+
+// Compare two files with `-nt` (newer than), `-ot` (older than) or `-ef` (same file)
+pub fn compare_files(op: &str, left: &str, right: &str) -> bool {
+    let left = fs::metadata(encoding::to_os_string(left)).ok();
+    let right = fs::metadata(encoding::to_os_string(right)).ok();
+    match (op, left, right) {
+        ("-nt", Some(left), Some(right)) => modification_time(&left) > modification_time(&right),
+        ("-ot", Some(left), Some(right)) => modification_time(&left) < modification_time(&right),
+        // A file which exists is newer than one which does not
+        ("-nt", Some(_), None) | ("-ot", None, Some(_)) => true,
+        ("-ef", Some(left), Some(right)) => left.dev() == right.dev() && left.ino() == right.ino(),
+        _ => false,
+    }
+}
+
+// Compare two integers with `-eq`, `-ne`, `-lt`, `-le`, `-gt` or `-ge`
+pub fn compare_integers(op: &str, left: i64, right: i64) -> bool {
+    match op {
+        "-eq" => left == right,
+        "-ne" => left != right,
+        "-lt" => left < right,
+        "-le" => left <= right,
+        "-gt" => left > right,
+        _ => left >= right,
+    }
+}
+
+impl Shell {
+    // Evaluate `[[ expression ]]` and return its exit status
+    // The status is 2 for an invalid regular expression, and 1 for other errors
+    pub fn evaluate_conditional(&mut self, expression: &ConditionalExpression) -> u8 {
+        match self.conditional_test(expression) {
+            Ok(true) => 0,
+            Ok(false) => 1,
+            Err(status) => status,
+        }
+    }
+
+    // A test with a single operand, including those which look at the shell itself
+    fn test_unary(&self, op: &str, operand: &str) -> bool {
+        match op {
+            "-v" => self.get_parameter(operand).is_some(),
+            "-o" => self.option(operand),
+            _ => unary_test(op, operand),
+        }
+    }
+
+    // Words inside `[[ ]]` are neither split nor globbed
+    fn expand_conditional_word(&mut self, word: &str) -> Result<String, u8> {
+        self.expand_word_to_string(word)
+            .map_err(|error| self.expansion_failed(error))
+    }
+
+    fn conditional_test(&mut self, expression: &ConditionalExpression) -> Result<bool, u8> {
+        Ok(match expression {
+            ConditionalExpression::Word(word) => !self.expand_conditional_word(word)?.is_empty(),
+            ConditionalExpression::Unary(op, operand) => {
+                let operand = self.expand_conditional_word(operand)?;
+                self.test_unary(op, &operand)
+            }
+            ConditionalExpression::Binary(op, left, right) => match op.as_str() {
+                // Both sides of the integer comparisons are arithmetic expressions
+                "-eq" | "-ne" | "-lt" | "-le" | "-gt" | "-ge" => {
+                    let left = self
+                        .expand_arithmetic(left)
+                        .map_err(|error| self.arithmetic_failed(error))?;
+                    let right = self
+                        .expand_arithmetic(right)
+                        .map_err(|error| self.arithmetic_failed(error))?;
+                    compare_integers(op, left, right)
+                }
+                // The right side is a pattern, unless quoted
+                "=" | "==" | "!=" => {
+                    let left = self.expand_conditional_word(left)?;
+                    let pattern = self
+                        .expand_pattern(right)
+                        .map_err(|error| self.expansion_failed(error))?;
+                    pattern_matches(&pattern, &left) == (op != "!=")
+                }
+                "=~" => {
+                    let left = self.expand_conditional_word(left)?;
+                    let regex = self
+                        .expand_regex(right)
+                        .map_err(|error| self.expansion_failed(error))?;
+                    let regex = Regex::new(&regex).map_err(|_| 2)?;
+                    // `BASH_REMATCH` holds the whole match followed by each group
+                    let mut groups = BTreeMap::new();
+                    if let Some(captures) = regex.captures(&left) {
+                        for (i, group) in captures.iter().enumerate() {
+                            let group = group.map_or("", |x| x.as_str());
+                            groups.insert(i as i64, group.to_owned());
+                        }
+                    }
+                    let matched = !groups.is_empty();
+                    self.variables
+                        .set_value("BASH_REMATCH", Value::Indexed(groups));
+                    matched
+                }
+                _ => {
+                    let left = self.expand_conditional_word(left)?;
+                    let right = self.expand_conditional_word(right)?;
+                    match op.as_str() {
+                        "<" => left < right,
+                        ">" => left > right,
+                        _ => compare_files(op, &left, &right),
+                    }
+                }
+            },
+            ConditionalExpression::Not(expression) => !self.conditional_test(expression)?,
+            ConditionalExpression::And(left, right) => {
+                self.conditional_test(left)? && self.conditional_test(right)?
+            }
+            ConditionalExpression::Or(left, right) => {
+                self.conditional_test(left)? || self.conditional_test(right)?
+            }
+        })
+    }
+}
+
+// Parse an integer operand of `test`, allowing blanks around it
+fn parse_integer(operand: &str) -> Result<i64, String> {
+    operand
+        .trim()
+        .parse()
+        .map_err(|_| format!("{}: integer expression expected", operand))
+}
+
+// A binary test of the `test` builtin, where both operands are plain strings
+fn test_binary(op: &str, left: &str, right: &str) -> Result<bool, String> {
+    Ok(match op {
+        "=" | "==" => left == right,
+        "!=" => left != right,
+        "<" => left < right,
+        ">" => left > right,
+        "-nt" | "-ot" | "-ef" => compare_files(op, left, right),
+        _ => compare_integers(op, parse_integer(left)?, parse_integer(right)?),
+    })
+}
+
+fn is_test_binary_operator(op: &str) -> bool {
+    op != "=~" && BINARY_OPERATORS.contains(&op)
+}
+
+impl Shell {
+    // Evaluate a test expression given as separate arguments
+    // Like POSIX asks, up to four arguments are decided by their count before falling back to parsing
+    pub fn evaluate_test(&self, args: &[String]) -> Result<bool, String> {
+        match args.len() {
+            0 => Ok(false),
+            1 => Ok(!args[0].is_empty()),
+            2 if args[0] == "!" => Ok(args[1].is_empty()),
+            2 if UNARY_OPERATORS.contains(&args[0].as_str()) => {
+                Ok(self.test_unary(&args[0], &args[1]))
+            }
+            2 => Err(format!("{}: unary operator expected", args[0])),
+            3 if is_test_binary_operator(&args[1]) => test_binary(&args[1], &args[0], &args[2]),
+            3 if args[1] == "-a" => Ok(!args[0].is_empty() && !args[2].is_empty()),
+            3 if args[1] == "-o" => Ok(!args[0].is_empty() || !args[2].is_empty()),
+            3 if args[0] == "!" => Ok(!self.evaluate_test(&args[1..])?),
+            3 if args[0] == "(" && args[2] == ")" => Ok(!args[1].is_empty()),
+            3 => Err(format!("{}: binary operator expected", args[1])),
+            4 if args[0] == "!" => Ok(!self.evaluate_test(&args[1..])?),
+            4 if args[0] == "(" && args[3] == ")" => self.evaluate_test(&args[1..3]),
+            _ => {
+                let mut pos = 0;
+                let result = self.test_or(args, &mut pos)?;
+                match args.get(pos) {
+                    None => Ok(result),
+                    Some(_) => Err("too many arguments".to_owned()),
+                }
+            }
+        }
+    }
+
+    // `-o` binds looser than `-a`, which binds looser than `!`
+    fn test_or(&self, args: &[String], pos: &mut usize) -> Result<bool, String> {
+        let mut result = self.test_and(args, pos)?;
+        while args.get(*pos).map(|x| x.as_str()) == Some("-o") {
+            *pos += 1;
+            result |= self.test_and(args, pos)?;
+        }
+        Ok(result)
+    }
+
+    fn test_and(&self, args: &[String], pos: &mut usize) -> Result<bool, String> {
+        let mut result = self.test_not(args, pos)?;
+        while args.get(*pos).map(|x| x.as_str()) == Some("-a") {
+            *pos += 1;
+            result &= self.test_not(args, pos)?;
+        }
+        Ok(result)
+    }
+
+    fn test_not(&self, args: &[String], pos: &mut usize) -> Result<bool, String> {
+        if args.get(*pos).map(|x| x.as_str()) == Some("!") {
+            *pos += 1;
+            return Ok(!self.test_not(args, pos)?);
+        }
+        self.test_primary(args, pos)
+    }
+
+    fn test_primary(&self, args: &[String], pos: &mut usize) -> Result<bool, String> {
+        let arg = match args.get(*pos) {
+            Some(arg) => arg.as_str(),
+            None => return Err("argument expected".to_owned()),
+        };
+
+        if arg == "(" {
+            *pos += 1;
+            let result = self.test_or(args, pos)?;
+            if args.get(*pos).map(|x| x.as_str()) != Some(")") {
+                return Err("`)' expected".to_owned());
+            }
+            *pos += 1;
+            return Ok(result);
+        }
+        if let Some(op) = args.get(*pos + 1) {
+            if is_test_binary_operator(op) && *pos + 2 < args.len() {
+                *pos += 3;
+                return test_binary(op, arg, &args[*pos - 1]);
+            }
+            if UNARY_OPERATORS.contains(&arg) {
+                *pos += 2;
+                return Ok(self.test_unary(arg, op));
+            }
+        }
+        *pos += 1;
+        Ok(!arg.is_empty())
+    }
+}
@@ -0,0 +1,60 @@
+// Text inside the shell is kept as `String`, but arguments, file names and the environment
+// can hold any bytes. Bytes which are not valid UTF-8 are carried in strings as code points
+// of a private use area, and turned back into the original bytes when they leave the shell.
+use std::ffi::{OsStr, OsString};
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+
+// The byte `b` is carried as the code point `RAW_BYTE_BASE + b`, only bytes from 0x80 can be invalid
+// The valid characters from `RAW_BYTE_BASE + 0x80` are carried as their own UTF-8 bytes in turn,
+// so that they are not mistaken for carried bytes
+const RAW_BYTE_BASE: u32 = 0x10FF00;
+
+fn raw_byte(c: char) -> Option<u8> {
+    let code = c as u32;
+    (code >= RAW_BYTE_BASE + 0x80).then(|| (code - RAW_BYTE_BASE) as u8)
+}
+
+// Read the bytes as a string, keeping those which are not valid UTF-8
+pub fn decode(bytes: &[u8]) -> String {
+    let mut text = String::with_capacity(bytes.len());
+    for chunk in bytes.utf8_chunks() {
+        for c in chunk.valid().chars() {
+            match raw_byte(c) {
+                // A character which would read back as a carried byte is carried as its own bytes
+                Some(_) => push_raw_bytes(&mut text, c.encode_utf8(&mut [0; 4]).as_bytes()),
+                None => text.push(c),
+            }
+        }
+        push_raw_bytes(&mut text, chunk.invalid());
+    }
+    text
+}
+
+fn push_raw_bytes(text: &mut String, bytes: &[u8]) {
+    for &byte in bytes {
+        text.extend(char::from_u32(RAW_BYTE_BASE + byte as u32));
+    }
+}
+
+// The bytes a string decoded by `decode` came from
+pub fn encode(text: &str) -> Vec<u8> {
+    if !text.chars().any(|c| raw_byte(c).is_some()) {
+        return text.as_bytes().to_vec();
+    }
+    let mut bytes = Vec::with_capacity(text.len());
+    for c in text.chars() {
+        match raw_byte(c) {
+            Some(byte) => bytes.push(byte),
+            None => bytes.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes()),
+        }
+    }
+    bytes
+}
+
+pub fn to_os_string(text: &str) -> OsString {
+    OsString::from_vec(encode(text))
+}
+
+pub fn from_os_str(text: &OsStr) -> String {
+    decode(text.as_bytes())
+}
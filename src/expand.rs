@@ -0,0 +1,987 @@
+use thiserror::Error;
+
+use crate::arith::ArithmeticError;
+use crate::lexer::{decode_ansi_c_quoted, find_arithmetic_end, find_closing_brace};
+use crate::parser::{is_valid_name, split_subscript};
+use crate::pattern::{escape_pattern, expand_pathname, has_wildcards, pattern_matches};
+use crate::shell::{single_quote, Shell};
+use crate::variables::Value;
+
+#[derive(Debug, Error)]
+pub enum ExpandError {
+    #[error("{0}: bad substitution")]
+    BadSubstitution(String),
+    #[error("${0}: cannot assign in this way")]
+    CannotAssign(String),
+    #[error("{0}: invalid indirect expansion")]
+    InvalidIndirect(String),
+    #[error("{0}: invalid variable name")]
+    InvalidName(String),
+    #[error("{0}: substring expression < 0")]
+    NegativeSubstring(String),
+    #[error(transparent)]
+    Arithmetic(#[from] ArithmeticError),
+    #[error("{0}: cannot assign list to array member")]
+    ListToElement(String),
+    #[error("{0}: must use subscript when assigning associative array")]
+    MustUseSubscript(String),
+    // Raised by `${var:?message}`
+    #[error("{0}: {1}")]
+    Unset(String, String),
+    // Raised by expanding an unset parameter with `set -u`
+    #[error("{0}: unbound variable")]
+    Unbound(String),
+}
+
+impl ExpandError {
+    // The status a shell running a script exits with after the error, for the errors about unset
+    // parameters of `${var:?message}` and `set -u`, and for invalid arithmetic expressions
+    pub fn exit_status(&self) -> Option<u8> {
+        match self {
+            ExpandError::Unset(..) => Some(127),
+            ExpandError::Unbound(_) | ExpandError::Arithmetic(_) => Some(1),
+            _ => None,
+        }
+    }
+}
+
+// The fields produced while expanding a single word
+// A word usually gives one field, but "$@" gives one per positional parameter
+#[derive(Default)]
+struct Fields {
+    fields: Vec<String>,
+    current: String,
+    // Whether the current field exists even if empty, like for `""`
+    has_current: bool,
+    // When expanding a pattern, quoted characters are escaped with this so that they match literally
+    escape: Option<fn(&str) -> String>,
+    // For pathname expansion, the current field as a pattern where the quoted characters are escaped,
+    // along with the patterns of the finished fields
+    pattern: Option<String>,
+    patterns: Vec<String>,
+    // Set when the last expansion was a list like "$@" without any values
+    empty_list: bool,
+    // The characters splitting the results of unquoted expansions, `None` where words are not split
+    ifs: Option<String>,
+    // Whether the last character added was part of a field delimiter, and if that was not only blanks
+    in_delimiter: bool,
+    delimiter_has_non_blank: bool,
+}
+
+impl Fields {
+    // Mark the current field as existing, even if it is still empty
+    fn start_field(&mut self) {
+        self.has_current = true;
+        self.in_delimiter = false;
+    }
+
+    fn push(&mut self, c: char) {
+        self.current.push(c);
+        if let Some(pattern) = &mut self.pattern {
+            pattern.push(c);
+        }
+        self.start_field();
+    }
+
+    fn push_str(&mut self, s: &str) {
+        self.current.push_str(s);
+        if let Some(pattern) = &mut self.pattern {
+            pattern.push_str(s);
+        }
+        self.start_field();
+    }
+
+    fn push_quoted(&mut self, c: char) {
+        self.push_quoted_str(c.encode_utf8(&mut [0; 4]));
+    }
+
+    fn push_quoted_str(&mut self, s: &str) {
+        match self.escape {
+            Some(escape) => self.current.push_str(&escape(s)),
+            None => self.current.push_str(s),
+        }
+        if let Some(pattern) = &mut self.pattern {
+            pattern.push_str(&escape_pattern(s));
+        }
+        self.start_field();
+    }
+
+    // Add the result of an expansion, which can only be special in a pattern if it was not quoted
+    // Unquoted results are split into fields, and vanish when empty
+    fn push_value(&mut self, s: &str, quoted: bool) {
+        match (quoted, self.ifs.clone()) {
+            (true, _) => self.push_quoted_str(s),
+            (false, Some(ifs)) => self.push_split(s, &ifs),
+            (false, None) => self.push_str(s),
+        }
+    }
+
+    // Add text split at the characters of IFS
+    // A run of IFS blanks ends a field, any other IFS character ends one even if it is empty
+    // and blanks around it are part of the same delimiter
+    fn push_split(&mut self, s: &str, ifs: &str) {
+        for c in s.chars() {
+            if !ifs.contains(c) {
+                self.push(c);
+            } else if matches!(c, ' ' | '\t' | '\n') {
+                if self.has_current {
+                    self.split();
+                    self.in_delimiter = true;
+                    self.delimiter_has_non_blank = false;
+                }
+            } else if self.in_delimiter && !self.delimiter_has_non_blank {
+                self.delimiter_has_non_blank = true;
+            } else {
+                self.end_field();
+                self.in_delimiter = true;
+                self.delimiter_has_non_blank = true;
+            }
+        }
+    }
+
+    // End the current field, if there is one, and start a new one
+    fn split(&mut self) {
+        if self.has_current {
+            self.end_field();
+        }
+    }
+
+    // Add the current field to the finished ones, even if it is empty
+    fn end_field(&mut self) {
+        self.fields.push(std::mem::take(&mut self.current));
+        if let Some(pattern) = &mut self.pattern {
+            self.patterns.push(std::mem::take(pattern));
+        }
+        self.has_current = false;
+    }
+
+    fn finish(mut self) -> Vec<String> {
+        self.split();
+        self.fields
+    }
+
+    // Replace the fields whose patterns match file names by the sorted names
+    // Fields without any match, or without any unquoted `*`, `?` or `[`, stay as they are
+    fn expand_pathnames(mut self) -> Vec<String> {
+        self.split();
+        let mut expanded = Vec::new();
+        for (field, pattern) in self.fields.into_iter().zip(self.patterns) {
+            let paths = match has_wildcards(&pattern) {
+                true => expand_pathname(&pattern),
+                false => Vec::new(),
+            };
+            match paths.is_empty() {
+                true => expanded.push(field),
+                false => expanded.extend(paths),
+            }
+        }
+        expanded
+    }
+}
+
+// Operators which can follow the parameter name in `${name<op>word}`, longest first
+const PARAMETER_OPERATORS: [&str; 12] = [
+    ":-", ":=", ":?", ":+", "##", "%%", "-", "=", "?", "+", "#", "%",
+];
+
+// Length of the parameter name at the start of the body of a `${ }`, including any `[subscript]`
+fn parameter_name_len(body: &str) -> usize {
+    match body.chars().next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => {
+            let len = body
+                .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+                .unwrap_or(body.len());
+            match body[len..].starts_with('[') {
+                true => body[len..].find(']').map_or(len, |end| len + end + 1),
+                false => len,
+            }
+        }
+        Some(c) if c.is_ascii_digit() => body
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(body.len()),
+        Some('#' | '?' | '$' | '!' | '-' | '@' | '*') => 1,
+        _ => 0,
+    }
+}
+
+// Whether the quoted expansion of the parameter joins its values into one field, like "$*"
+fn joins_values(name: &str) -> bool {
+    name == "*" || name.ends_with("[*]")
+}
+
+// Remove the shortest or longest prefix of the value matching the pattern
+fn remove_prefix(value: &str, pattern: &str, longest: bool) -> String {
+    let mut ends: Vec<usize> = value.char_indices().map(|(i, _)| i).collect();
+    ends.push(value.len());
+    if longest {
+        ends.reverse();
+    }
+    match ends
+        .into_iter()
+        .find(|&end| pattern_matches(pattern, &value[..end]))
+    {
+        Some(end) => value[end..].to_owned(),
+        None => value.to_owned(),
+    }
+}
+
+// Remove the shortest or longest suffix of the value matching the pattern
+fn remove_suffix(value: &str, pattern: &str, longest: bool) -> String {
+    let mut starts: Vec<usize> = value.char_indices().map(|(i, _)| i).collect();
+    starts.push(value.len());
+    if !longest {
+        starts.reverse();
+    }
+    match starts
+        .into_iter()
+        .find(|&start| pattern_matches(pattern, &value[start..]))
+    {
+        Some(start) => value[..start].to_owned(),
+        None => value.to_owned(),
+    }
+}
+
+// Which occurrences of the pattern `${name/pattern/replacement}` replaces
+#[derive(Clone, Copy, PartialEq)]
+enum Replace {
+    First,
+    All,
+    Prefix,
+    Suffix,
+}
+
+// Replace the longest match of the pattern in the value
+fn replace_pattern(value: &str, pattern: &str, replacement: &str, mode: Replace) -> String {
+    let mut boundaries: Vec<usize> = value.char_indices().map(|(i, _)| i).collect();
+    boundaries.push(value.len());
+
+    match mode {
+        Replace::Prefix => {
+            match boundaries
+                .iter()
+                .rev()
+                .find(|&&end| pattern_matches(pattern, &value[..end]))
+            {
+                Some(&end) => format!("{}{}", replacement, &value[end..]),
+                None => value.to_owned(),
+            }
+        }
+        Replace::Suffix => {
+            match boundaries
+                .iter()
+                .find(|&&start| pattern_matches(pattern, &value[start..]))
+            {
+                Some(&start) => format!("{}{}", &value[..start], replacement),
+                None => value.to_owned(),
+            }
+        }
+        Replace::First | Replace::All => {
+            if pattern.is_empty() {
+                return value.to_owned();
+            }
+            let mut result = String::new();
+            let mut i = 0;
+            while i + 1 < boundaries.len() {
+                let start = boundaries[i];
+                // The longest non empty match starting here
+                let matched = (i + 1..boundaries.len())
+                    .rev()
+                    .find(|&j| pattern_matches(pattern, &value[start..boundaries[j]]));
+                match matched {
+                    Some(j) => {
+                        result.push_str(replacement);
+                        i = j;
+                        if mode == Replace::First {
+                            break;
+                        }
+                    }
+                    None => {
+                        result.push_str(&value[start..boundaries[i + 1]]);
+                        i += 1;
+                    }
+                }
+            }
+            result.push_str(&value[boundaries[i]..]);
+            result
+        }
+    }
+}
+
+// Change the case of the first character, or of all of them, if they match the pattern
+fn convert_case(value: &str, upper: bool, all: bool, pattern: Option<&str>) -> String {
+    let mut result = String::with_capacity(value.len());
+    for (i, c) in value.chars().enumerate() {
+        let selected = (all || i == 0)
+            && pattern.map_or(true, |pattern| {
+                pattern_matches(pattern, c.encode_utf8(&mut [0; 4]))
+            });
+        match (selected, upper) {
+            (true, true) => result.extend(c.to_uppercase()),
+            (true, false) => result.extend(c.to_lowercase()),
+            (false, _) => result.push(c),
+        }
+    }
+    result
+}
+
+// Find the first occurrence of the character outside quotes and not escaped by a backslash
+fn find_unquoted(word: &str, target: char) -> Option<usize> {
+    let mut it = word.char_indices();
+    let mut in_double_quotes = false;
+    while let Some((i, c)) = it.next() {
+        match c {
+            '\\' => {
+                it.next();
+            }
+            '\'' if !in_double_quotes => {
+                it.find(|&(_, c)| c == '\'');
+            }
+            '"' => in_double_quotes = !in_double_quotes,
+            c if c == target && !in_double_quotes => return Some(i),
+            _ => {}
+        }
+    }
+    None
+}
+
+impl Shell {
+    // Expand a word into fields: tilde expansion, parameter expansion, field splitting,
+    // pathname expansion unless `set -f` is used, and quote removal
+    pub fn expand_word(&mut self, word: &str) -> Result<Vec<String>, ExpandError> {
+        let globbing = !self.option("noglob");
+        let mut fields = Fields {
+            ifs: Some(self.variables.get("IFS").unwrap_or(" \t\n").to_owned()),
+            pattern: globbing.then(String::new),
+            ..Default::default()
+        };
+        self.expand_into(word, &mut fields)?;
+        match globbing {
+            true => Ok(fields.expand_pathnames()),
+            false => Ok(fields.finish()),
+        }
+    }
+
+    // Expand a word in a place where it is never split, like the value of an assignment
+    pub fn expand_word_to_string(&mut self, word: &str) -> Result<String, ExpandError> {
+        let mut fields = Fields::default();
+        self.expand_into(word, &mut fields)?;
+        Ok(fields.finish().join(" "))
+    }
+
+    // Expand the parameters in an arithmetic expression as if it were double quoted, then evaluate it
+    pub fn expand_arithmetic(&mut self, expression: &str) -> Result<i64, ExpandError> {
+        let expression = self.expand_inner_word(expression, true, None)?;
+        Ok(self.evaluate_arithmetic(&expression)?)
+    }
+
+    // Expand a word used as a glob pattern, where the quoted parts only match literally
+    pub fn expand_pattern(&mut self, word: &str) -> Result<String, ExpandError> {
+        self.expand_inner_word(word, false, Some(escape_pattern))
+    }
+
+    // Expand a word used as a regular expression, where the quoted parts only match literally
+    pub fn expand_regex(&mut self, word: &str) -> Result<String, ExpandError> {
+        self.expand_inner_word(word, false, Some(regex::escape))
+    }
+
+    // Expand the body of a here-document, which is like a double quoted string where `"` is not special
+    pub fn expand_here_document(&mut self, body: &str) -> Result<String, ExpandError> {
+        let mut fields = Fields::default();
+        let mut ind = 0;
+        while let Some(c) = body[ind..].chars().next() {
+            ind += c.len_utf8();
+            match c {
+                '\\' => match body[ind..].chars().next() {
+                    Some(next @ ('$' | '`' | '\\')) => {
+                        fields.push_quoted(next);
+                        ind += 1;
+                    }
+                    Some('\n') => ind += 1,
+                    _ => fields.push_quoted('\\'),
+                },
+                '$' => ind = self.expand_dollar(body, ind, true, &mut fields)?,
+                _ => fields.push_quoted(c),
+            }
+        }
+        Ok(fields.finish().join(" "))
+    }
+
+    // Expand the word used inside a `${ }`, which follows the quoting of the `${ }` itself
+    // With `escape` the quoted characters of the result are escaped
+    fn expand_inner_word(
+        &mut self,
+        word: &str,
+        quoted: bool,
+        escape: Option<fn(&str) -> String>,
+    ) -> Result<String, ExpandError> {
+        let mut fields = Fields {
+            escape,
+            ..Default::default()
+        };
+        if quoted {
+            // Inside double quotes, any `"` in the word only toggles quoting which changes nothing here
+            let mut ind = 0;
+            while ind < word.len() {
+                ind = self.expand_double_quoted(word, ind, &mut fields)?;
+            }
+        } else {
+            self.expand_into(word, &mut fields)?;
+        }
+        Ok(fields.finish().join(" "))
+    }
+
+    fn expand_into(&mut self, word: &str, fields: &mut Fields) -> Result<(), ExpandError> {
+        let mut ind = 0;
+
+        // A leading `~` is the home directory
+        if word == "~" || word.starts_with("~/") {
+            let home = self.variables.get("HOME").unwrap_or("").to_owned();
+            fields.push_quoted_str(&home);
+            ind = 1;
+        }
+
+        while let Some(c) = word[ind..].chars().next() {
+            ind += c.len_utf8();
+            match c {
+                '\\' => {
+                    // Backslash outside quotes preserves the next character, except a newline which it removes
+                    if word[ind..].starts_with('\n') {
+                        ind += 1;
+                    } else if let Some(next) = word[ind..].chars().next() {
+                        fields.push_quoted(next);
+                        ind += next.len_utf8();
+                    }
+                }
+                '\'' => {
+                    let end = ind + word[ind..].find('\'').unwrap_or(word.len() - ind);
+                    fields.push_quoted_str(&word[ind..end]);
+                    ind = (end + 1).min(word.len());
+                }
+                '"' => ind = self.expand_double_quoted(word, ind, fields)?,
+                '$' => ind = self.expand_dollar(word, ind, false, fields)?,
+                _ => fields.push(c),
+            }
+        }
+        Ok(())
+    }
+
+    // Expand the inside of a double quoted string starting at `ind`, returning the index after the closing `"`
+    fn expand_double_quoted(
+        &mut self,
+        word: &str,
+        mut ind: usize,
+        fields: &mut Fields,
+    ) -> Result<usize, ExpandError> {
+        let start = ind;
+        // Where a list expansion without values ended, when it started the string
+        let mut empty_list_end = None;
+        while let Some(c) = word[ind..].chars().next() {
+            ind += c.len_utf8();
+            match c {
+                '"' => break,
+                '\\' => {
+                    // Backslash before the below characters preserves the special meaning of these
+                    match word[ind..].chars().next() {
+                        Some(next @ ('$' | '`' | '"' | '\\')) => {
+                            fields.push_quoted(next);
+                            ind += 1;
+                        }
+                        Some('\n') => ind += 1,
+                        _ => fields.push_quoted('\\'),
+                    }
+                }
+                '$' => {
+                    fields.empty_list = false;
+                    let dollar = ind - 1;
+                    ind = self.expand_dollar(word, ind, true, fields)?;
+                    if dollar == start && fields.empty_list {
+                        empty_list_end = Some(ind);
+                    }
+                }
+                _ => fields.push_quoted(c),
+            }
+        }
+
+        // "" is an empty field, but "$@" or "${a[@]}" without any values is no field at all
+        if empty_list_end != Some(ind - 1) {
+            fields.start_field();
+        }
+        Ok(ind)
+    }
+
+    // Expand the parameter after a `$`, where `ind` is the index right after the `$`
+    // Returns the index after the expansion
+    fn expand_dollar(
+        &mut self,
+        word: &str,
+        ind: usize,
+        quoted: bool,
+        fields: &mut Fields,
+    ) -> Result<usize, ExpandError> {
+        let rest = &word[ind..];
+        let next = match rest.chars().next() {
+            Some(c) => c,
+            None => {
+                fields.push('$');
+                return Ok(ind);
+            }
+        };
+
+        // `$'...'` and `$"..."` are only special outside double quotes
+        if next == '\'' && !quoted {
+            let (value, end) = decode_ansi_c_quoted(word, ind + 1)
+                .unwrap_or_else(|| (word[ind + 1..].to_owned(), word.len()));
+            fields.push_quoted_str(&value);
+            return Ok(end);
+        }
+        if next == '"' && !quoted {
+            return self.expand_double_quoted(word, ind + 1, fields);
+        }
+
+        if next == '{' {
+            let end = match find_closing_brace(word, ind + 1) {
+                Some(end) => end,
+                None => return Err(ExpandError::BadSubstitution(word[ind - 1..].to_owned())),
+            };
+            self.expand_braced(&word[ind + 1..end], quoted, fields)?;
+            return Ok(end + 1);
+        }
+
+        if rest.starts_with("((") {
+            let end = match find_arithmetic_end(word, ind + 2) {
+                Some(end) => end,
+                None => return Err(ExpandError::BadSubstitution(word[ind - 1..].to_owned())),
+            };
+            let value = self.expand_arithmetic(&word[ind + 2..end])?;
+            fields.push_value(&value.to_string(), quoted);
+            return Ok(end + 2);
+        }
+
+        if next.is_ascii_alphabetic() || next == '_' {
+            let len = rest
+                .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+                .unwrap_or(rest.len());
+            let value = self.set_parameter(&rest[..len])?;
+            fields.push_value(&value, quoted);
+            return Ok(ind + len);
+        }
+
+        match next {
+            '@' | '*' => self.expand_positional(&rest[..1], quoted, fields),
+            // Only a single digit is read here, `${10}` is needed for the tenth parameter
+            '0'..='9' | '#' | '?' | '$' | '!' | '-' => {
+                let value = self.set_parameter(&rest[..1])?;
+                fields.push_value(&value, quoted);
+            }
+            _ => {
+                // A lone `$` is kept as it is
+                fields.push('$');
+                return Ok(ind);
+            }
+        }
+        Ok(ind + 1)
+    }
+
+    // Expand the body of a `${ }`, like `name`, `#name` or `name:-default`
+    fn expand_braced(
+        &mut self,
+        body: &str,
+        quoted: bool,
+        fields: &mut Fields,
+    ) -> Result<(), ExpandError> {
+        let bad_substitution = || ExpandError::BadSubstitution(format!("${{{}}}", body));
+
+        // `${#name}` is the length of the value, in characters
+        if body.len() > 1
+            && body.starts_with('#')
+            && parameter_name_len(&body[1..]) == body.len() - 1
+        {
+            let name = self.resolve_subscript(&body[1..])?;
+            let length = match self.list_values(&name) {
+                Some(values) => values.len(),
+                None => self.set_parameter(&name)?.chars().count(),
+            };
+            fields.push_value(&length.to_string(), quoted);
+            return Ok(());
+        }
+
+        // `${!prefix*}` and `${!prefix@}` are the names of the variables starting with the prefix
+        if let Some(prefix) = body.strip_prefix('!') {
+            if let Some(prefix) = prefix.strip_suffix(['*', '@']) {
+                if is_valid_name(prefix) {
+                    let names: Vec<String> = self
+                        .variables
+                        .names()
+                        .into_iter()
+                        .filter(|name| name.starts_with(prefix))
+                        .map(|name| name.to_owned())
+                        .collect();
+                    self.expand_values(&names, body.ends_with('*') && quoted, quoted, fields);
+                    return Ok(());
+                }
+            }
+        }
+
+        // `${!name[@]}` and `${!name[*]}` are the keys of an array
+        if let Some((name, subscript @ ("@" | "*"))) =
+            body.strip_prefix('!').and_then(split_subscript)
+        {
+            let keys = match self.variables.get_value(name) {
+                Some(value) => value.keys(),
+                None => Vec::new(),
+            };
+            self.expand_values(&keys, subscript == "*" && quoted, quoted, fields);
+            return Ok(());
+        }
+
+        // `${!name}` expands the parameter named by the value of `name`,
+        // but `${!-word}` and `${!?word}` are operators on `$!`
+        let (indirect, body) = match body.strip_prefix('!') {
+            Some(rest) if rest.len() > 1 && rest.starts_with(['-', '?']) => (false, body),
+            Some(rest) if parameter_name_len(rest) > 0 => (true, rest),
+            _ => (false, body),
+        };
+        let (name, rest) = body.split_at(parameter_name_len(body));
+        if name.is_empty() {
+            return Err(bad_substitution());
+        }
+        let name = if indirect {
+            match self.get_parameter(name) {
+                Some(target)
+                    if !target.is_empty() && parameter_name_len(&target) == target.len() =>
+                {
+                    target
+                }
+                Some(target) if !target.is_empty() => {
+                    return Err(ExpandError::InvalidName(target));
+                }
+                _ => return Err(ExpandError::InvalidIndirect(name.to_owned())),
+            }
+        } else {
+            name.to_owned()
+        };
+        let name = self.resolve_subscript(&name)?;
+        let name = name.as_str();
+
+        if rest.is_empty() {
+            if let Some(values) = self.list_values(name) {
+                self.expand_values(&values, joins_values(name) && quoted, quoted, fields);
+            } else {
+                let value = self.set_parameter(name)?;
+                fields.push_value(&value, quoted);
+            }
+            return Ok(());
+        }
+
+        // `${name:offset:length}`, told apart from `${name:-word}` and the like
+        if let Some(spec) = rest.strip_prefix(':') {
+            if !PARAMETER_OPERATORS
+                .iter()
+                .any(|op| op.starts_with(':') && rest.starts_with(op))
+            {
+                return self.expand_substring(name, spec, quoted, fields);
+            }
+        }
+
+        match rest.chars().next() {
+            // `${name/pattern/replacement}` and its `//`, `/#` and `/%` variants
+            Some('/') => {
+                let spec = &rest[1..];
+                let (mode, spec) = match spec.chars().next() {
+                    Some('/') => (Replace::All, &spec[1..]),
+                    Some('#') => (Replace::Prefix, &spec[1..]),
+                    Some('%') => (Replace::Suffix, &spec[1..]),
+                    _ => (Replace::First, spec),
+                };
+                let (pattern, replacement) = match find_unquoted(spec, '/') {
+                    Some(ind) => (&spec[..ind], &spec[ind + 1..]),
+                    None => (spec, ""),
+                };
+                let pattern = self.expand_pattern(pattern)?;
+                let replacement = self.expand_inner_word(replacement, quoted, None)?;
+                return self.push_transformed(name, quoted, fields, |value| {
+                    replace_pattern(value, &pattern, &replacement, mode)
+                });
+            }
+            // `${name^}` and `${name^^}` for upper case, `${name,}` and `${name,,}` for lower case
+            // An optional pattern limits the characters which are converted
+            Some(c @ ('^' | ',')) => {
+                let all = rest[1..].starts_with(c);
+                let pattern = &rest[if all { 2 } else { 1 }..];
+                let pattern = match pattern.is_empty() {
+                    true => None,
+                    false => Some(self.expand_pattern(pattern)?),
+                };
+                return self.push_transformed(name, quoted, fields, |value| {
+                    convert_case(value, c == '^', all, pattern.as_deref())
+                });
+            }
+            // `${name@Q}` quotes the value so that it can be read back by the shell
+            Some('@') => {
+                if &rest[1..] != "Q" {
+                    return Err(bad_substitution());
+                }
+                return self.push_transformed(name, quoted, fields, single_quote);
+            }
+            _ => {}
+        }
+
+        let op = match PARAMETER_OPERATORS.iter().find(|op| rest.starts_with(**op)) {
+            Some(op) => *op,
+            None => return Err(bad_substitution()),
+        };
+        let word = &rest[op.len()..];
+        let value = self.get_parameter(name);
+
+        // Without the colon only an unset parameter counts, with it an empty one does too
+        let is_missing = match value.as_deref() {
+            None => true,
+            Some("") => op.starts_with(':'),
+            Some(_) => false,
+        };
+
+        match op {
+            ":-" | "-" => {
+                let value = match is_missing {
+                    true => self.expand_inner_word(word, quoted, None)?,
+                    false => value.unwrap(),
+                };
+                fields.push_value(&value, quoted);
+            }
+            ":=" | "=" => {
+                let value = match is_missing {
+                    true => {
+                        if !is_valid_name(name) {
+                            return Err(ExpandError::CannotAssign(name.to_owned()));
+                        }
+                        let value = self.expand_inner_word(word, quoted, None)?;
+                        self.variables.set(name, &value);
+                        value
+                    }
+                    false => value.unwrap(),
+                };
+                fields.push_value(&value, quoted);
+            }
+            ":?" | "?" => {
+                if is_missing {
+                    let mut message = self.expand_inner_word(word, quoted, None)?;
+                    if message.is_empty() {
+                        message = "parameter null or not set".to_owned();
+                    }
+                    return Err(ExpandError::Unset(name.to_owned(), message));
+                }
+                fields.push_value(&value.unwrap(), quoted);
+            }
+            ":+" | "+" => {
+                let value = match is_missing {
+                    true => String::new(),
+                    false => self.expand_inner_word(word, quoted, None)?,
+                };
+                fields.push_value(&value, quoted);
+            }
+            _ => {
+                // The pattern removal operators `#`, `##`, `%` and `%%`
+                // The pattern is not affected by double quotes around the whole `${ }`
+                let pattern = self.expand_pattern(word)?;
+                self.push_transformed(name, quoted, fields, |value| match op {
+                    "#" => remove_prefix(value, &pattern, false),
+                    "##" => remove_prefix(value, &pattern, true),
+                    "%" => remove_suffix(value, &pattern, false),
+                    _ => remove_suffix(value, &pattern, true),
+                })?;
+            }
+        }
+        Ok(())
+    }
+
+    // Add the transformed value of the parameter
+    // For `@`, `*` and whole arrays every value is transformed on its own
+    fn push_transformed(
+        &self,
+        name: &str,
+        quoted: bool,
+        fields: &mut Fields,
+        transform: impl Fn(&str) -> String,
+    ) -> Result<(), ExpandError> {
+        if let Some(values) = self.list_values(name) {
+            let values: Vec<String> = values.iter().map(|x| transform(x)).collect();
+            self.expand_values(&values, joins_values(name) && quoted, quoted, fields);
+        } else {
+            let value = match self.get_parameter(name) {
+                Some(value) => transform(&value),
+                // An unset parameter stays empty, unless `nounset` makes it an error
+                None => self.set_parameter(name)?,
+            };
+            fields.push_value(&value, quoted);
+        }
+        Ok(())
+    }
+
+    // Expand `${name:offset}` or `${name:offset:length}`, counting in characters
+    // A negative offset counts from the end and a negative length leaves out characters at the end
+    fn expand_substring(
+        &mut self,
+        name: &str,
+        spec: &str,
+        quoted: bool,
+        fields: &mut Fields,
+    ) -> Result<(), ExpandError> {
+        let (offset, length) = match spec.split_once(':') {
+            Some((offset, length)) => (offset, Some(length)),
+            None => (spec, None),
+        };
+        let offset = self.expand_arithmetic(offset)?;
+        let length = match length {
+            Some(length) => Some((length, self.expand_arithmetic(length)?)),
+            None => None,
+        };
+
+        // Slice the values between `start` and `end`, given their count
+        let bounds = |count: usize| -> Result<Option<(usize, usize)>, ExpandError> {
+            let count = count as i64;
+            let start = if offset < 0 { count + offset } else { offset };
+            if start < 0 || start > count {
+                return Ok(None);
+            }
+            let end = match length {
+                None => count,
+                Some((_, length)) if length >= 0 => (start + length).min(count),
+                Some((text, length)) => {
+                    let end = count + length;
+                    if end < start {
+                        return Err(ExpandError::NegativeSubstring(text.trim().to_owned()));
+                    }
+                    end
+                }
+            };
+            Ok(Some((start as usize, end as usize)))
+        };
+
+        if let Some(entries) = self.list_entries(name) {
+            if let Some((_, length)) = length {
+                if length < 0 {
+                    return Err(ExpandError::NegativeSubstring(length.to_string()));
+                }
+            }
+            // The offset is an index, which for a sparse array is not the same as the position
+            let end = entries.last().map_or(0, |(index, _)| index + 1);
+            let start = if offset < 0 { end + offset } else { offset };
+            let values: Vec<String> = entries
+                .into_iter()
+                .filter(|(index, _)| start >= 0 && *index >= start)
+                .map(|(_, value)| value)
+                .take(length.map_or(usize::MAX, |(_, length)| length as usize))
+                .collect();
+            self.expand_values(&values, joins_values(name) && quoted, quoted, fields);
+        } else {
+            let value = self.set_parameter(name)?;
+            let chars: Vec<char> = value.chars().collect();
+            let substring: String = match bounds(chars.len())? {
+                Some((start, end)) => chars[start..end].iter().collect(),
+                None => String::new(),
+            };
+            fields.push_value(&substring, quoted);
+        }
+        Ok(())
+    }
+
+    // Expand `$@` or `$*`, where "$@" gives a separate field for every positional parameter
+    fn expand_positional(&self, name: &str, quoted: bool, fields: &mut Fields) {
+        let positional = self.positional.clone();
+        self.expand_values(&positional, name == "*" && quoted, quoted, fields);
+    }
+
+    // Turn `name[subscript]` into `name[key]` with the key of the element
+    // Other parameters, including `name[@]` and `name[*]`, are left as they are
+    fn resolve_subscript(&mut self, name: &str) -> Result<String, ExpandError> {
+        match split_subscript(name) {
+            Some((base, subscript)) if subscript != "@" && subscript != "*" => {
+                let subscript = self.expand_word_to_string(subscript)?;
+                Ok(format!("{}[{}]", base, self.array_key(base, &subscript)?))
+            }
+            _ => Ok(name.to_owned()),
+        }
+    }
+
+    // The values of `@`, `*`, `name[@]` or `name[*]` with their indices, `None` for other parameters
+    // The positional parameters are counted from `$0`
+    fn list_entries(&self, name: &str) -> Option<Vec<(i64, String)>> {
+        if name == "@" || name == "*" {
+            let mut values = vec![self.shell_name.clone()];
+            values.extend(self.positional.iter().cloned());
+            return Some((0..).zip(values).collect());
+        }
+        match split_subscript(name)? {
+            (name, "@" | "*") => Some(match self.variables.get_value(name) {
+                Some(Value::Indexed(array)) => array.iter().map(|(i, x)| (*i, x.clone())).collect(),
+                Some(value) => (0..).zip(value.values()).collect(),
+                None => Vec::new(),
+            }),
+            _ => None,
+        }
+    }
+
+    // The values of `@`, `*`, `name[@]` or `name[*]`, `None` for other parameters
+    fn list_values(&self, name: &str) -> Option<Vec<String>> {
+        if name == "@" || name == "*" {
+            return Some(self.positional.clone());
+        }
+        let entries = self.list_entries(name)?;
+        Some(entries.into_iter().map(|(_, value)| value).collect())
+    }
+
+    // Add a list of values either joined into one field or as a field each
+    // Joined values are separated by the first character of IFS
+    fn expand_values(&self, values: &[String], join: bool, quoted: bool, fields: &mut Fields) {
+        if join {
+            let separator = match self.variables.get("IFS") {
+                Some(ifs) => ifs.chars().next().map(String::from).unwrap_or_default(),
+                None => " ".to_owned(),
+            };
+            fields.push_value(&values.join(&separator), quoted);
+            return;
+        }
+        fields.empty_list = values.is_empty();
+        for (i, value) in values.iter().enumerate() {
+            if i > 0 {
+                fields.split();
+            }
+            fields.push_value(value, quoted);
+        }
+    }
+
+    // Value of a parameter being expanded, where an unset one is empty or with `nounset` an error
+    fn set_parameter(&self, name: &str) -> Result<String, ExpandError> {
+        match self.get_parameter(name) {
+            Some(value) => Ok(value),
+            None if self.option("nounset") => match name.parse::<usize>() {
+                Ok(_) => Err(ExpandError::Unbound(format!("${}", name))),
+                Err(_) => Err(ExpandError::Unbound(name.to_owned())),
+            },
+            None => Ok(String::new()),
+        }
+    }
+
+    // Value of a variable, positional parameter or special parameter, `None` if it is unset
+    pub fn get_parameter(&self, name: &str) -> Option<String> {
+        if let Ok(position) = name.parse::<usize>() {
+            if position == 0 {
+                return Some(self.shell_name.clone());
+            }
+            return self.positional.get(position - 1).cloned();
+        }
+        match name {
+            "#" => Some(self.positional.len().to_string()),
+            "?" => Some(self.last_status.to_string()),
+            "$" => Some(self.pid.to_string()),
+            "!" => self.last_background_pid().map(|pid| pid.to_string()),
+            "-" => Some(self.option_flags()),
+            "@" | "*" => Some(self.positional.join(" ")),
+            _ => match split_subscript(name) {
+                Some((name, "@" | "*")) => Some(self.variables.get_value(name)?.values().join(" ")),
+                Some((name, key)) => Some(self.variables.get_value(name)?.element(key)?.to_owned()),
+                None => self.variables.get(name).map(|x| x.to_owned()),
+            },
+        }
+    }
+}
@@ -0,0 +1,93 @@
+// The command line of the shell itself, like `-c commands` or `--rcfile file script args`
+use crate::shell;
+
+// Printed by `--help`
+pub const USAGE: &str = "\
+Usage: codecrafters-shell [option ...] [script [argument ...]]
+       codecrafters-shell [option ...] -c commands [name [argument ...]]
+       codecrafters-shell [option ...] -s [argument ...]
+Long options:
+    --help --version --login --posix --norc --noprofile --rcfile file
+Shell options:
+    -ilsc (invocation only)
+    -abefux or -o option, set with `-` and unset with `+`";
+
+#[derive(Debug, Default)]
+pub struct Invocation {
+    // `-c`, the first operand is then the commands to run
+    pub command: bool,
+    // `-s`, read the commands from the input even if there are operands
+    pub stdin: bool,
+    // `-i`, interactive even if the input is not a terminal
+    pub interactive: bool,
+    // `-l` or `--login`
+    pub login: bool,
+    // `--posix`, where interactive shells read the file named by `ENV` instead of the rc file
+    pub posix: bool,
+    pub no_rc: bool,
+    pub no_profile: bool,
+    pub help: bool,
+    pub version: bool,
+    // `--rcfile file`, read instead of `~/.<shellname>rc`
+    pub rc_file: Option<String>,
+    // Options of `set` given as `-e` or `-o errexit`, turned off by `+e` or `+o errexit`
+    pub options: Vec<(String, bool)>,
+    // The arguments after the options: the script or the commands, then the positional parameters
+    pub operands: Vec<String>,
+}
+
+impl Invocation {
+    // Parse the arguments, without the name of the program
+    pub fn parse(args: &[String]) -> Result<Invocation, String> {
+        let mut invocation = Invocation::default();
+        let mut ind = 0;
+        while let Some(arg) = args.get(ind) {
+            ind += 1;
+            match arg.as_str() {
+                "--" => break,
+                "--help" => invocation.help = true,
+                "--version" => invocation.version = true,
+                "--login" => invocation.login = true,
+                "--posix" => invocation.posix = true,
+                "--norc" => invocation.no_rc = true,
+                "--noprofile" => invocation.no_profile = true,
+                "--rcfile" | "--init-file" => match args.get(ind) {
+                    Some(file) => {
+                        invocation.rc_file = Some(file.clone());
+                        ind += 1;
+                    }
+                    None => return Err(format!("{}: option requires an argument", arg)),
+                },
+                _ if arg.starts_with("--") => return Err(format!("{}: invalid option", arg)),
+                _ if (arg.starts_with('-') || arg.starts_with('+')) && arg.len() > 1 => {
+                    let on = arg.starts_with('-');
+                    for c in arg[1..].chars() {
+                        match c {
+                            'c' => invocation.command = true,
+                            's' => invocation.stdin = true,
+                            'i' => invocation.interactive = true,
+                            'l' => invocation.login = true,
+                            'o' => match args.get(ind) {
+                                Some(name) => {
+                                    invocation.options.push((name.clone(), on));
+                                    ind += 1;
+                                }
+                                None => return Err("-o: option requires an argument".to_owned()),
+                            },
+                            _ => match shell::option_name(c) {
+                                Some(name) => invocation.options.push((name.to_owned(), on)),
+                                None => return Err(format!("{}{}: invalid option", &arg[..1], c)),
+                            },
+                        }
+                    }
+                }
+                _ => {
+                    ind -= 1;
+                    break;
+                }
+            }
+        }
+        invocation.operands = args[ind..].to_vec();
+        Ok(invocation)
+    }
+}
@@ -0,0 +1,283 @@
+// Commands run in the background with `&`, which the shell keeps track of as jobs
+// Each job is a single child process leading its own process group, so that a signal can reach
+// every process the job started
+use std::collections::HashMap;
+
+use libc::{c_int, pid_t};
+
+use crate::signal;
+use crate::sys;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum JobState {
+    Running,
+    Stopped(c_int),
+    Exited(u8),
+    // The signal which killed the process and whether it dumped core
+    Signaled(c_int, bool),
+}
+
+impl JobState {
+    fn from_wait_status(status: c_int) -> JobState {
+        if libc::WIFSTOPPED(status) {
+            JobState::Stopped(libc::WSTOPSIG(status))
+        } else if libc::WIFCONTINUED(status) {
+            JobState::Running
+        } else if libc::WIFSIGNALED(status) {
+            JobState::Signaled(libc::WTERMSIG(status), libc::WCOREDUMP(status))
+        } else {
+            JobState::Exited(libc::WEXITSTATUS(status) as u8)
+        }
+    }
+
+    // Whether the process is gone, as opposed to running or stopped
+    pub fn is_terminated(&self) -> bool {
+        matches!(self, JobState::Exited(_) | JobState::Signaled(..))
+    }
+
+    // The status like `$?` would have, 128 + the signal number for a stopped or killed process
+    pub fn status(&self) -> u8 {
+        match self {
+            JobState::Running => 0,
+            JobState::Exited(code) => *code,
+            JobState::Stopped(signal) | JobState::Signaled(signal, _) => 128 + *signal as u8,
+        }
+    }
+
+    // How `jobs` shows the state, like `Running`, `Exit 2` or `Killed`
+    pub fn describe(&self) -> String {
+        match self {
+            JobState::Running => "Running".to_owned(),
+            JobState::Stopped(libc::SIGSTOP) => "Stopped (signal)".to_owned(),
+            JobState::Stopped(libc::SIGTTIN) => "Stopped (tty input)".to_owned(),
+            JobState::Stopped(libc::SIGTTOU) => "Stopped (tty output)".to_owned(),
+            JobState::Stopped(_) => "Stopped".to_owned(),
+            JobState::Exited(0) => "Done".to_owned(),
+            JobState::Exited(code) => format!("Exit {}", code),
+            JobState::Signaled(signal, false) => signal::description(*signal),
+            JobState::Signaled(signal, true) => {
+                format!("{} (core dumped)", signal::description(*signal))
+            }
+        }
+    }
+}
+
+pub struct Job {
+    pub id: usize,
+    // The pid of the child process, which is also its process group
+    pub pid: pid_t,
+    pub command: String,
+    pub state: JobState,
+    // Set by `disown -h`, the job is then not sent SIGHUP when the shell exits
+    pub no_hangup: bool,
+    // Whether the last change of state was announced, or the job was listed since
+    pub notified: bool,
+}
+
+#[derive(Default)]
+pub struct Jobs {
+    // Ordered by id
+    jobs: Vec<Job>,
+    // Ids from the least to the most recently started or stopped job,
+    // the last one is the current job `%+` and the one before it the previous job `%-`
+    recent: Vec<usize>,
+    // Statuses of the background processes whose job is gone, for `wait pid`
+    finished: HashMap<pid_t, u8>,
+    // The pid of the last job started, `$!`
+    pub last_pid: Option<pid_t>,
+}
+
+impl Jobs {
+    // Add a job for the child process, returning its id which is one more than the highest in use
+    pub fn add(&mut self, pid: pid_t, command: &str) -> usize {
+        let id = self.jobs.last().map_or(1, |job| job.id + 1);
+        self.jobs.push(Job {
+            id,
+            pid,
+            command: command.to_owned(),
+            state: JobState::Running,
+            no_hangup: false,
+            notified: true,
+        });
+        self.recent.push(id);
+        self.last_pid = Some(pid);
+        id
+    }
+
+    pub fn get(&self, id: usize) -> Option<&Job> {
+        self.jobs.iter().find(|job| job.id == id)
+    }
+
+    pub fn ids(&self) -> Vec<usize> {
+        self.jobs.iter().map(|job| job.id).collect()
+    }
+
+    pub fn get_mut(&mut self, id: usize) -> Option<&mut Job> {
+        self.jobs.iter_mut().find(|job| job.id == id)
+    }
+
+    pub fn any_stopped(&self) -> bool {
+        self.jobs
+            .iter()
+            .any(|job| matches!(job.state, JobState::Stopped(_)))
+    }
+
+    // The ids of the jobs whose process is gone
+    pub fn terminated(&self) -> Vec<usize> {
+        self.jobs
+            .iter()
+            .filter(|job| job.state.is_terminated())
+            .map(|job| job.id)
+            .collect()
+    }
+
+    // The id of the job running as the process
+    pub fn with_pid(&self, pid: pid_t) -> Option<usize> {
+        self.jobs
+            .iter()
+            .find(|job| job.pid == pid)
+            .map(|job| job.id)
+    }
+
+    // The status of a background process whose job is already gone
+    pub fn finished_status(&self, pid: pid_t) -> Option<u8> {
+        self.finished.get(&pid).copied()
+    }
+
+    // Remove the job, remembering the status of its process if it terminated
+    pub fn remove(&mut self, id: usize) -> Option<Job> {
+        let ind = self.jobs.iter().position(|job| job.id == id)?;
+        let job = self.jobs.remove(ind);
+        self.recent.retain(|&recent| recent != id);
+        if job.state.is_terminated() {
+            self.finished.insert(job.pid, job.state.status());
+        }
+        Some(job)
+    }
+
+    // Forget every job, done in forked children which can not wait for the jobs of their parent
+    // `$!` stays the same
+    pub fn clear(&mut self) {
+        *self = Jobs {
+            last_pid: self.last_pid,
+            ..Jobs::default()
+        };
+    }
+
+    // `+` for the current job, `-` for the previous one, else a space
+    pub fn marker(&self, id: usize) -> char {
+        let mut recent = self.recent.iter().rev();
+        if recent.next() == Some(&id) {
+            '+'
+        } else if recent.next() == Some(&id) {
+            '-'
+        } else {
+            ' '
+        }
+    }
+
+    // Find the job named by a spec like `%1`, `%%`, `%+`, `%-`, `%name` or `%?text`
+    // `%name` is the job whose command starts with the name, `%?text` the one whose command contains the text
+    pub fn find(&self, spec: &str) -> Result<usize, String> {
+        let no_such_job = || format!("{}: no such job", spec);
+        let name = spec.strip_prefix('%').ok_or_else(no_such_job)?;
+        let id = match name {
+            "" | "%" | "+" => self.recent.last().copied(),
+            // With a single job it is both the current and the previous job
+            "-" => self
+                .recent
+                .iter()
+                .rev()
+                .nth(1)
+                .or(self.recent.last())
+                .copied(),
+            _ if name.bytes().all(|b| b.is_ascii_digit()) => {
+                name.parse().ok().filter(|&id| self.get(id).is_some())
+            }
+            _ => {
+                let matches: Vec<usize> = self
+                    .jobs
+                    .iter()
+                    .filter(|job| match name.strip_prefix('?') {
+                        Some(text) => job.command.contains(text),
+                        None => job.command.starts_with(name),
+                    })
+                    .map(|job| job.id)
+                    .collect();
+                if matches.len() > 1 {
+                    return Err(format!("{}: ambiguous job spec", name));
+                }
+                matches.first().copied()
+            }
+        };
+        id.ok_or_else(no_such_job)
+    }
+
+    // Check the processes of the jobs which are still around, without waiting for them
+    // A job which stops becomes the current job
+    pub fn reap(&mut self) {
+        for job in &mut self.jobs {
+            // A job can both continue and terminate before it is checked
+            while !job.state.is_terminated() {
+                let Ok(Some(status)) = sys::poll_pid(job.pid) else {
+                    break;
+                };
+                job.state = JobState::from_wait_status(status);
+                job.notified = job.state == JobState::Running;
+                if let JobState::Stopped(_) = job.state {
+                    let id = job.id;
+                    self.recent.retain(|&recent| recent != id);
+                    self.recent.push(id);
+                }
+            }
+        }
+    }
+
+    // How `jobs` lists the job, with its pid when `long`
+    pub fn format(&self, job: &Job, long: bool) -> String {
+        let marker = self.marker(job.id);
+        let prefix = match long {
+            true => format!("[{}]{} {} ", job.id, marker, job.pid),
+            false => format!("[{}]{}  ", job.id, marker),
+        };
+        let command = match job.state {
+            JobState::Running => format!("{} &", job.command),
+            _ => job.command.clone(),
+        };
+        format!("{}{:<23} {}", prefix, job.state.describe(), command)
+    }
+
+    // The lines announcing the jobs which stopped or terminated since they were last announced,
+    // like `[1]+  Done                    make`, after which the terminated jobs are forgotten
+    // Without job control only the jobs killed by a signal other than INT, TERM or PIPE are announced,
+    // with their pid like ` 1234 Killed                  make`
+    pub fn notifications(&mut self, interactive: bool) -> Vec<String> {
+        self.reap();
+        let mut lines = Vec::new();
+        for job in self.jobs.iter().filter(|job| !job.notified) {
+            match job.state {
+                _ if interactive => lines.push(self.format(job, false)),
+                JobState::Signaled(signal, _)
+                    if !matches!(signal, libc::SIGINT | libc::SIGTERM | libc::SIGPIPE) =>
+                {
+                    lines.push(format!(
+                        "{:5} {:<23} {}",
+                        job.pid,
+                        job.state.describe(),
+                        job.command
+                    ));
+                }
+                _ => {}
+            }
+        }
+        for job in &mut self.jobs {
+            job.notified = true;
+        }
+        if interactive {
+            for id in self.terminated() {
+                self.remove(id);
+            }
+        }
+        lines
+    }
+}
@@ -0,0 +1,462 @@
+use crate::encoding;
+use crate::parser::{split_assignment, ParseError};
+
+// Operators recognised by the lexer, longest first so that `>>` wins over `>`
+const OPERATORS: [&str; 18] = [
+    "&>>", "<<-", "&&", "||", ">>", ">|", ">&", "<&", "<>", "&>", "<<", ";", "&", "|", "(", ")",
+    "<", ">",
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Token {
+    // A word with its quotes still in place, quote removal happens during expansion
+    Word(String),
+    // The digits right before a redirection operator, like the `2` in `2>`
+    IoNumber(u32),
+    Op(&'static str),
+    // The expression of an arithmetic command `(( expr ))`
+    Arithmetic(String),
+    Newline,
+    Eof,
+}
+
+pub struct Lexer<'a> {
+    input: &'a str,
+    pos: usize,
+    // End of the here-document bodies following the current line, skipped at its newline
+    here_document_end: Option<usize>,
+    // Whether a `#` at the start of a word begins a comment running to the end of the line
+    comments: bool,
+}
+
+impl<'a> Lexer<'a> {
+    pub fn new(input: &'a str) -> Self {
+        Self {
+            input,
+            pos: 0,
+            here_document_end: None,
+            comments: true,
+        }
+    }
+
+    pub fn set_comments(&mut self, comments: bool) {
+        self.comments = comments;
+    }
+
+    pub fn input(&self) -> &'a str {
+        self.input
+    }
+
+    fn peek_char(&self) -> Option<char> {
+        self.input[self.pos..].chars().next()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek_char()?;
+        self.pos += c.len_utf8();
+        Some(c)
+    }
+
+    // Return the next token along with the byte offsets it spans in the input
+    pub fn next_token(&mut self) -> Result<(Token, usize, usize), ParseError> {
+        loop {
+            let rest = &self.input[self.pos..];
+            if rest.starts_with([' ', '\t']) {
+                self.bump();
+            } else if rest.starts_with("\\\n") {
+                // A backslash before a newline joins the lines
+                self.pos += 2;
+                if self.pos == self.input.len() {
+                    return Err(ParseError::UnexpectedEof);
+                }
+            } else if self.comments && rest.starts_with('#') {
+                // The newline ending the comment is still a token
+                self.pos += rest.find('\n').unwrap_or(rest.len());
+            } else {
+                break;
+            }
+        }
+
+        let start = self.pos;
+        let rest = &self.input[self.pos..];
+        let token = match self.peek_char() {
+            None => Token::Eof,
+            Some('\n') => {
+                self.bump();
+                if let Some(end) = self.here_document_end.take() {
+                    self.pos = end;
+                }
+                Token::Newline
+            }
+            Some(_) => {
+                // `((` starts an arithmetic command when it has a matching `))`, else it is two `(`
+                let arithmetic_end = if rest.starts_with("((") {
+                    find_arithmetic_end(self.input, self.pos + 2)
+                } else {
+                    None
+                };
+                if let Some(end) = arithmetic_end {
+                    let expression = self.input[self.pos + 2..end].to_owned();
+                    self.pos = end + 2;
+                    Token::Arithmetic(expression)
+                } else if let Some(op) = OPERATORS.iter().find(|op| rest.starts_with(**op)) {
+                    self.pos += op.len();
+                    Token::Op(op)
+                } else {
+                    let word = self.read_word()?;
+                    // A word made only of digits directly followed by `<` or `>` is a file descriptor
+                    let next = self.peek_char();
+                    if (next == Some('<') || next == Some('>'))
+                        && !word.is_empty()
+                        && word.bytes().all(|b| b.is_ascii_digit())
+                    {
+                        match word.parse::<u32>() {
+                            Ok(fd) => Token::IoNumber(fd),
+                            Err(_) => Token::Word(word),
+                        }
+                    } else {
+                        Token::Word(word)
+                    }
+                }
+            }
+        };
+        Ok((token, start, self.pos))
+    }
+
+    // Read the regular expression on the right of `=~` in `[[ ]]` as a single word
+    // Parentheses and `|` belong to the expression, it only ends at a blank or an unbalanced `)`
+    pub fn read_regex(&mut self) -> Result<(Token, usize, usize), ParseError> {
+        while let Some(' ' | '\t') = self.peek_char() {
+            self.bump();
+        }
+
+        let start = self.pos;
+        let mut depth = 0;
+        while let Some(c) = self.peek_char() {
+            match c {
+                ' ' | '\t' | '\n' => break,
+                ')' if depth == 0 => break,
+                '(' => depth += 1,
+                ')' => depth -= 1,
+                '\\' => {
+                    self.bump();
+                }
+                '\'' => {
+                    self.bump();
+                    self.skip_single_quoted()?;
+                    continue;
+                }
+                '"' => {
+                    self.bump();
+                    self.skip_double_quoted()?;
+                    continue;
+                }
+                '$' => {
+                    self.bump();
+                    self.skip_unquoted_dollar()?;
+                    continue;
+                }
+                _ => {}
+            }
+            self.bump();
+        }
+
+        if self.pos == start {
+            return self.next_token();
+        }
+        let word = self.input[start..self.pos].to_owned();
+        Ok((Token::Word(word), start, self.pos))
+    }
+
+    // Read a word until an unquoted metacharacter, keeping the quotes intact
+    fn read_word(&mut self) -> Result<String, ParseError> {
+        let start = self.pos;
+        while let Some(c) = self.peek_char() {
+            match c {
+                // The parentheses of an array assignment like `a=(x y)` are part of the word
+                '(' if split_assignment(&self.input[start..self.pos])
+                    .is_some_and(|(_, _, value)| value.is_empty()) =>
+                {
+                    self.bump();
+                    self.skip_array_elements()?;
+                }
+                ' ' | '\t' | '\n' | ';' | '&' | '|' | '(' | ')' | '<' | '>' => break,
+                '\\' => {
+                    self.bump();
+                    // The line continues after a trailing backslash
+                    let escaped = self.bump();
+                    if escaped.is_none() || (escaped == Some('\n') && self.pos == self.input.len())
+                    {
+                        return Err(ParseError::UnexpectedEof);
+                    }
+                }
+                '\'' => {
+                    self.bump();
+                    self.skip_single_quoted()?;
+                }
+                '"' => {
+                    self.bump();
+                    self.skip_double_quoted()?;
+                }
+                '$' => {
+                    self.bump();
+                    self.skip_unquoted_dollar()?;
+                }
+                _ => {
+                    self.bump();
+                }
+            }
+        }
+        Ok(self.input[start..self.pos].to_owned())
+    }
+
+    // Read the body of a here-document whose delimiter was the last word read
+    // The body starts on the line after the current one, or after the previous body read on this line
+    // With `strip_tabs`, for `<<-`, the leading tabs of every line are removed
+    pub fn read_here_document(
+        &mut self,
+        delimiter: &str,
+        strip_tabs: bool,
+    ) -> Result<String, ParseError> {
+        let mut pos = match self.here_document_end {
+            Some(end) => end,
+            None => match self.input[self.pos..].find('\n') {
+                Some(ind) => self.pos + ind + 1,
+                None => return Err(ParseError::HereDocument(delimiter.to_owned())),
+            },
+        };
+
+        let mut body = String::new();
+        loop {
+            if pos >= self.input.len() {
+                return Err(ParseError::HereDocument(delimiter.to_owned()));
+            }
+            let line_end = self.input[pos..]
+                .find('\n')
+                .map_or(self.input.len(), |ind| pos + ind);
+            let mut line = &self.input[pos..line_end];
+            if strip_tabs {
+                line = line.trim_start_matches('\t');
+            }
+            pos = (line_end + 1).min(self.input.len());
+            if line == delimiter {
+                break;
+            }
+            body.push_str(line);
+            body.push('\n');
+        }
+        self.here_document_end = Some(pos);
+        Ok(body)
+    }
+
+    // Skip to the character after the `)` closing the elements of an array assignment
+    fn skip_array_elements(&mut self) -> Result<(), ParseError> {
+        loop {
+            match self.bump() {
+                Some(')') => return Ok(()),
+                Some('\\') => {
+                    self.bump();
+                }
+                Some('\'') => self.skip_single_quoted()?,
+                Some('"') => self.skip_double_quoted()?,
+                Some('$') => self.skip_unquoted_dollar()?,
+                Some(_) => continue,
+                None => return Err(ParseError::Unterminated(')')),
+            }
+        }
+    }
+
+    // Skip to the character after the closing `'`
+    fn skip_single_quoted(&mut self) -> Result<(), ParseError> {
+        loop {
+            match self.bump() {
+                Some('\'') => return Ok(()),
+                Some(_) => continue,
+                None => return Err(ParseError::Unterminated('\'')),
+            }
+        }
+    }
+
+    // Skip to the character after the closing `"`
+    fn skip_double_quoted(&mut self) -> Result<(), ParseError> {
+        loop {
+            match self.bump() {
+                Some('"') => return Ok(()),
+                Some('\\') => {
+                    self.bump();
+                }
+                Some('$') => self.skip_dollar()?,
+                Some(_) => continue,
+                None => return Err(ParseError::Unterminated('"')),
+            }
+        }
+    }
+
+    // Like `skip_dollar` but also skipping `$'...'`, which is only special outside double quotes
+    fn skip_unquoted_dollar(&mut self) -> Result<(), ParseError> {
+        if self.peek_char() != Some('\'') {
+            return self.skip_dollar();
+        }
+        match decode_ansi_c_quoted(self.input, self.pos + 1) {
+            Some((_, end)) => {
+                self.pos = end;
+                Ok(())
+            }
+            None => Err(ParseError::Unterminated('\'')),
+        }
+    }
+
+    // Skip the body of an expansion starting right after a `$`
+    fn skip_dollar(&mut self) -> Result<(), ParseError> {
+        if self.peek_char() == Some('{') {
+            self.bump();
+            match find_closing_brace(self.input, self.pos) {
+                Some(end) => self.pos = end + 1,
+                None => return Err(ParseError::Unterminated('}')),
+            }
+        } else if self.input[self.pos..].starts_with("((") {
+            match find_arithmetic_end(self.input, self.pos + 2) {
+                Some(end) => self.pos = end + 2,
+                None => return Err(ParseError::Unterminated(')')),
+            }
+        }
+        Ok(())
+    }
+}
+
+// Find the `}` closing a `${` whose body starts at `start`
+// Quotes and nested `${ }` inside the body are skipped over
+pub fn find_closing_brace(input: &str, start: usize) -> Option<usize> {
+    let mut it = input[start..].char_indices().peekable();
+    let mut depth = 0;
+    let mut in_double_quotes = false;
+    while let Some((i, c)) = it.next() {
+        match c {
+            '\\' => {
+                it.next();
+            }
+            '\'' if !in_double_quotes => loop {
+                match it.next() {
+                    Some((_, '\'')) => break,
+                    Some(_) => continue,
+                    None => return None,
+                }
+            },
+            '"' => in_double_quotes = !in_double_quotes,
+            '$' if it.peek().map(|&(_, c)| c) == Some('{') => {
+                it.next();
+                depth += 1;
+            }
+            '}' if depth == 0 => return Some(start + i),
+            '}' => depth -= 1,
+            _ => {}
+        }
+    }
+    None
+}
+
+// Find the `))` closing a `((` whose expression starts at `start`, returning the index of its first `)`
+// Parentheses inside the expression have to balance
+pub fn find_arithmetic_end(input: &str, start: usize) -> Option<usize> {
+    let mut depth = 0;
+    let bytes = input.as_bytes();
+    for i in start..bytes.len() {
+        match bytes[i] {
+            b'(' => depth += 1,
+            b')' if depth > 0 => depth -= 1,
+            b')' if bytes.get(i + 1) == Some(&b')') => return Some(i),
+            b')' => return None,
+            _ => {}
+        }
+    }
+    None
+}
+
+// Decode the ANSI-C quoted string `$'...'` whose body starts at `start`
+// Returns the decoded string and the index after the closing `'`, or `None` if it is not closed
+// Like in C, the string stops at an escaped NUL byte
+pub fn decode_ansi_c_quoted(input: &str, start: usize) -> Option<(String, usize)> {
+    let bytes = input.as_bytes();
+    // Read up to `max` digits in the given radix, `None` if there are none
+    let read_digits = |i: &mut usize, radix: u32, max: usize| {
+        let len = bytes[*i..]
+            .iter()
+            .take(max)
+            .take_while(|b| (**b as char).is_digit(radix))
+            .count();
+        let digits = &input[*i..*i + len];
+        *i += len;
+        u32::from_str_radix(digits, radix).ok()
+    };
+
+    let mut decoded = Vec::new();
+    let mut i = start;
+    while let Some(&c) = bytes.get(i) {
+        i += 1;
+        if c == b'\'' {
+            if let Some(nul) = decoded.iter().position(|&b| b == 0) {
+                decoded.truncate(nul);
+            }
+            return Some((encoding::decode(&decoded), i));
+        }
+        if c >= 0x80 {
+            // Characters carrying bytes which are not UTF-8 give back those bytes
+            let c = input[i - 1..].chars().next()?;
+            i += c.len_utf8() - 1;
+            decoded.extend(encoding::encode(c.encode_utf8(&mut [0; 4])));
+            continue;
+        }
+        if c != b'\\' {
+            decoded.push(c);
+            continue;
+        }
+
+        let escaped = *bytes.get(i)?;
+        i += 1;
+        match escaped {
+            b'a' => decoded.push(0x07),
+            b'b' => decoded.push(0x08),
+            b'e' | b'E' => decoded.push(0x1b),
+            b'f' => decoded.push(0x0c),
+            b'n' => decoded.push(b'\n'),
+            b'r' => decoded.push(b'\r'),
+            b't' => decoded.push(b'\t'),
+            b'v' => decoded.push(0x0b),
+            b'\\' | b'\'' | b'"' | b'?' => decoded.push(escaped),
+            // Up to three octal digits, the value wraps around to a single byte
+            b'0'..=b'7' => {
+                i -= 1;
+                decoded.push(read_digits(&mut i, 8, 3).unwrap_or(0) as u8);
+            }
+            b'x' => match read_digits(&mut i, 16, 2) {
+                Some(value) => decoded.push(value as u8),
+                None => decoded.extend_from_slice(b"\\x"),
+            },
+            // A Unicode code point, written out in UTF-8
+            b'u' | b'U' => {
+                let max = if escaped == b'u' { 4 } else { 8 };
+                match read_digits(&mut i, 16, max) {
+                    Some(value) => {
+                        let c = char::from_u32(value).unwrap_or(char::REPLACEMENT_CHARACTER);
+                        decoded.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+                    }
+                    None => decoded.extend_from_slice(&[b'\\', escaped]),
+                }
+            }
+            // A control character, like `\cA` for 0x01
+            b'c' => match bytes.get(i) {
+                Some(b'?') => {
+                    i += 1;
+                    decoded.push(0x7f);
+                }
+                Some(&control) if control.is_ascii() => {
+                    i += 1;
+                    decoded.push(control & 0x1f);
+                }
+                _ => decoded.extend_from_slice(b"\\c"),
+            },
+            _ => decoded.extend_from_slice(&[b'\\', escaped]),
+        }
+    }
+    None
+}
@@ -1,177 +1,306 @@
+use rustyline::completion::Completer;
 use rustyline::error::ReadlineError;
-use rustyline::{DefaultEditor, Result};
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::history::DefaultHistory;
+use rustyline::validate::{ValidationContext, ValidationResult, Validator};
+use rustyline::{Editor, ExternalPrinter, Helper, Result};
+use std::env;
+use std::fs::File;
+use std::io::{self, BufRead, IsTerminal, Read, Write};
+use std::path::Path;
 use std::process::ExitCode;
-use std::{
-    fs::{File, OpenOptions},
-    io::{self, Write},
-};
+use std::sync::{Arc, Mutex};
+use std::thread;
 
+mod arith;
+mod assign;
+mod condition;
+mod encoding;
+mod expand;
+mod invocation;
+mod jobs;
+mod lexer;
+mod parser;
+mod pattern;
 mod shell;
+mod signal;
+mod sys;
+mod variables;
+use invocation::{Invocation, USAGE};
+use jobs::Jobs;
 use shell::Shell;
 
-// Handle redirection of output of a command to stdout or to a file
-fn get_stdout_stream_path(parsed_args: &mut Vec<&str>) -> Box<dyn Write> {
-    // dbg!(&parsed_args);
-    let result = parsed_args
-        .iter()
-        .find(|&&x| x == "1>" || x == ">" || x == ">>" || x == "1>>");
-    if result.is_some() && parsed_args.len() >= 3 {
-        let file_name = parsed_args.last().unwrap();
-        // Choose whether to truncate a file or append to a file
-        let file = match result {
-            Some(&">") | Some(&"1>") => File::create(file_name).unwrap(), // This is also a wrapper over `OpenOptions`
-            Some(&">>") | Some(&"1>>") => OpenOptions::new()
-                .append(true)
-                .create(true)
-                .open(file_name)
-                .unwrap(),
-            Some(_) | None => panic!("Not possible"),
-        };
-        parsed_args.pop(); // Remove the file path
-        parsed_args.pop(); // Remove the " "
-        parsed_args.pop(); // Remove the redirection symbol
-        parsed_args.pop(); // Needed for running tests; will be `None` outside tests
-        Box::new(file)
-    } else {
-        Box::new(io::stdout())
+// Keeps the editor reading lines while the input is an incomplete command
+// like an open quote, a trailing `|` or an `if` without its `fi`,
+// so that the whole block is edited as one unit and run once it is complete
+struct InputHelper {
+    // The expanded `PS2`, written before each continuation line when the editor only reads the lines,
+    // like on a dumb terminal. The line editor lays out the cursor from the typed text alone,
+    // so it can not draw a prompt between the lines it edits
+    continuation_prompt: Option<String>,
+}
+
+impl Validator for InputHelper {
+    fn validate(&self, ctx: &mut ValidationContext) -> Result<ValidationResult> {
+        if !parser::is_incomplete(ctx.input()) {
+            return Ok(ValidationResult::Valid(None));
+        }
+        if let Some(prompt) = &self.continuation_prompt {
+            let mut stdout = io::stdout();
+            stdout.write_all(prompt.as_bytes())?;
+            stdout.flush()?;
+        }
+        Ok(ValidationResult::Incomplete)
     }
 }
 
-// Handle redirection of error of a command to stderr or to a file
-fn get_stderr_stream_path(parsed_args: &mut Vec<&str>) -> Box<dyn Write> {
-    let result = parsed_args.iter().find(|&&x| x == "2>" || x == "2>>");
-    if result.is_some() && parsed_args.len() >= 3 {
-        let file_name = parsed_args.last().unwrap();
-        // Choose whether to truncate a file or append to a file
-        let file = match result {
-            Some(&"2>") => File::create(file_name).unwrap(), // This is also a wrapper over `OpenOptions`
-            Some(&"2>>") => OpenOptions::new()
-                .append(true)
-                .create(true)
-                .open(file_name)
-                .unwrap(),
-            Some(_) | None => panic!("Not possible"),
+impl Completer for InputHelper {
+    type Candidate = String;
+}
+
+impl Hinter for InputHelper {
+    type Hint = String;
+}
+
+impl Highlighter for InputHelper {}
+
+impl Helper for InputHelper {}
+
+fn main() -> Result<ExitCode> {
+    let args: Vec<String> = std::env::args_os()
+        .map(|arg| encoding::from_os_str(&arg))
+        .collect();
+    let mut shell = Shell::new();
+    let invocation = match Invocation::parse(&args[1..]) {
+        Ok(invocation) => invocation,
+        Err(message) => {
+            shell.print_error(&message);
+            return Ok(ExitCode::from(2));
+        }
+    };
+    if invocation.help {
+        println!("{}", USAGE);
+        return Ok(ExitCode::SUCCESS);
+    }
+    if invocation.version {
+        println!("codecrafters-shell, version {}", env!("CARGO_PKG_VERSION"));
+        return Ok(ExitCode::SUCCESS);
+    }
+    if invocation.posix {
+        shell.set_option("posix", true);
+    }
+    for (name, on) in &invocation.options {
+        if !shell.set_option(name, *on) {
+            shell.print_error(&format!("{}: invalid option name", name));
+            return Ok(ExitCode::from(2));
+        }
+    }
+
+    // With `-c` the first operand is the commands to run and the next one is `$0`,
+    // else without `-s` it is a script to run, the other operands are the positional parameters
+    let mut operands = invocation.operands.iter().cloned();
+    let mut commands = None;
+    let mut script = None;
+    if invocation.command {
+        commands = match operands.next() {
+            Some(commands) => Some(commands),
+            None => {
+                shell.print_error("-c: option requires an argument");
+                return Ok(ExitCode::from(2));
+            }
         };
-        parsed_args.pop(); // Remove the file path
-        parsed_args.pop(); // Remove the " "
-        parsed_args.pop(); // Remove the redirection symbol
-        parsed_args.pop(); // Needed for running tests; will be `None` outside tests
-        Box::new(file)
+        if let Some(name) = operands.next() {
+            shell.shell_name = name;
+        }
+    } else if !invocation.stdin {
+        script = operands.next();
+    }
+    shell.positional = operands.collect();
+    shell.interactive = invocation.interactive
+        || (commands.is_none() && script.is_none() && io::stdin().is_terminal());
+    if commands.is_some() {
+        shell.input_flag = Some('c');
+    } else if script.is_none() {
+        shell.input_flag = Some('s');
+    }
+
+    run_startup_files(&mut shell, &invocation, &args[0]);
+    let status = if let Some(status_code) = shell.exit_code() {
+        status_code
+    } else if let Some(commands) = commands {
+        shell.run_source(&commands)
+    } else if let Some(script) = script {
+        shell.run_script(&script)
+    } else if shell.interactive {
+        run_interactive(&mut shell)?
     } else {
-        Box::new(io::stderr())
+        run_non_interactive(&mut shell)
+    };
+    // The trap on EXIT runs however the shell ends
+    let status = shell.finish(status);
+    shell.hang_up_jobs();
+    Ok(ExitCode::from(status))
+}
+
+// Source the startup files which exist: the profiles for a login shell, else the rc file for an interactive one
+// A login shell is started with `-l`, or by a program which puts a `-` before the name of the shell
+fn run_startup_files(shell: &mut Shell, invocation: &Invocation, program: &str) {
+    let login = invocation.login || program.starts_with('-');
+    let home = shell.variables.get("HOME").map(|home| home.to_owned());
+    let mut files = Vec::new();
+    if login {
+        if !invocation.no_profile {
+            files.push("/etc/profile".to_owned());
+            files.extend(home.as_ref().map(|home| format!("{}/.profile", home)));
+        }
+    } else if shell.interactive && shell.option("posix") {
+        // POSIX shells read the file named by `ENV` after expanding it
+        if let Some(env) = shell.variables.get("ENV").map(|env| env.to_owned()) {
+            files.extend(shell.expand_word_to_string(&env).ok());
+        }
+    } else if shell.interactive && !invocation.no_rc {
+        let name = program.rsplit('/').next().unwrap_or(program);
+        let rc_file = match &invocation.rc_file {
+            Some(rc_file) => Some(rc_file.clone()),
+            None => home.map(|home| format!("{}/.{}rc", home, name.trim_start_matches('-'))),
+        };
+        files.extend(rc_file);
+    }
+
+    for file in files {
+        if Path::new(&encoding::to_os_string(&file)).is_file() {
+            let _ = shell.source_file(&file);
+        }
+        if shell.exit_code().is_some() {
+            return;
+        }
     }
 }
 
-// Parse the input string, removing unnecessary spaces, quotes, etc.
-// Handles repeated spaces, single quotes inside double quotes, and many such cases.
-fn parse_input(args: &str) -> Vec<&str> {
-    let mut output: Vec<&str> = Vec::new();
-
-    // Make this peekable to allow peeking the values
-    // in the case where the current character is part of a word
-    let mut it = args.chars().enumerate().peekable();
-    while let Some((ind1, c)) = it.next() {
-        if c == '\'' {
-            let (ind2, _) = it.find(|(_, x)| x == &'\'').unwrap();
-            output.push(&args[(ind1 + 1)..ind2]);
-        } else if c == '"' {
-            let mut prev_ind1 = ind1 + 1; // After the `"`
-
-            while let Some((_ind2, _c2)) = it.next() {
-                if _c2 == '"' {
-                    output.push(&args[prev_ind1.._ind2]);
-                    break;
-                } else if _c2 == '\\' {
-                    // Backslash before the below 4 characters preserves the special meaning of these
-                    let (_ind3, _c3) = it.next().unwrap();
-                    if _c3 == '\n' || _c3 == '$' || _c3 == '"' || _c3 == '\\' {
-                        output.push(&args[prev_ind1.._ind2]);
-                        output.push(&args[_ind3..(_ind3 + 1)]);
-                        prev_ind1 = _ind3 + 1;
-                    }
-                }
+// Read commands from a pipe or a file without any prompt, line editing or history
+// Lines are collected until they make up complete commands, which then run together
+fn run_non_interactive(shell: &mut Shell) -> u8 {
+    let mut stdin = io::stdin().lock();
+    let mut source = String::new();
+    let mut line = Vec::new();
+    loop {
+        line.clear();
+        match stdin.read_until(b'\n', &mut line) {
+            Ok(0) | Err(_) => break,
+            Ok(_) => source += &encoding::decode(&line),
+        }
+        if parser::is_incomplete(&source) {
+            continue;
+        }
+
+        shell.run_source(&source);
+        source.clear();
+        if let Some(status_code) = shell.exit_code() {
+            return status_code;
+        }
+    }
+
+    // What is left can only be an incomplete command, which gets reported
+    if !source.is_empty() {
+        shell.run_source(&source);
+    }
+    shell.last_status
+}
+
+// Start a thread announcing the jobs lent to it as soon as they stop or terminate, for `set -b`
+// It is woken up by SIGCHLD and prints through the editor, which redraws the line being edited below
+fn spawn_job_notifier(
+    mut printer: impl ExternalPrinter + Send + 'static,
+) -> io::Result<Arc<Mutex<Option<Jobs>>>> {
+    let (read_end, write_end) = sys::pipe()?;
+    signal::wake_on_child(write_end)?;
+    let lent_jobs = Arc::new(Mutex::new(None::<Jobs>));
+    let jobs = Arc::clone(&lent_jobs);
+    thread::spawn(move || {
+        let mut wakeups = File::from(read_end);
+        let mut buf = [0; 64];
+        loop {
+            match wakeups.read(&mut buf) {
+                Ok(0) => break,
+                Ok(_) => {}
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(_) => break,
             }
-        } else if c.is_ascii_alphabetic()
-            || c.is_ascii_digit()
-            || c == '/'
-            || c == '.'
-            || c == '>' // For redirecting to a file
-            || c == '-'
-        // For `ls -1`
-        {
-            let mut ind2 = args.len(); // Default value is end of string
-
-            // Check the next value before moving the iterator
-            while let Some(&(i, c)) = it.peek() {
-                if c == '\'' || c == '"' || c == ' ' || c == '\\' {
-                    ind2 = i;
-                    break;
-                }
-                it.next();
+            let Ok(mut jobs) = jobs.lock() else {
+                break;
+            };
+            for line in jobs
+                .as_mut()
+                .map_or(Vec::new(), |jobs| jobs.notifications(true))
+            {
+                let _ = printer.print(line + "\n");
             }
-            output.push(&args[ind1..ind2]);
-        } else if c == ' ' && !output.is_empty() && output.last().unwrap() != &" " {
-            output.push(" ");
-        } else if c == '\\' {
-            // Handle cases like "a\ \ b"
-            let _ind = it.next().unwrap().0;
-            output.push(&args[_ind..(_ind + 1)]); // Push the &str at _ind
         }
-    }
-    output
+    });
+    Ok(lent_jobs)
 }
 
-fn main() -> Result<ExitCode> {
-    let mut rl = DefaultEditor::new()?;
+// Read commands from the user until `exit` or the end of the input
+// The jobs which stopped or terminated are announced before every prompt
+fn run_interactive(shell: &mut Shell) -> Result<u8> {
+    let mut rl: Editor<InputHelper, DefaultHistory> = Editor::new()?;
+    rl.set_helper(Some(InputHelper {
+        continuation_prompt: None,
+    }));
+    // On these terminals rustyline writes the prompt and reads the lines without editing them
+    let plain_reader = matches!(env::var("TERM").as_deref(), Ok("dumb" | "cons25" | "emacs"));
+    // Without a terminal there is no line being edited, so `set -b` waits for the prompt too
+    let notifier = rl
+        .create_external_printer()
+        .ok()
+        .and_then(|printer| spawn_job_notifier(printer).ok());
 
     loop {
+        shell.notify_jobs();
+        let lent_jobs = notifier.as_ref().filter(|_| shell.option("notify"));
+        if let Some(lent_jobs) = lent_jobs {
+            *lent_jobs.lock().unwrap() = Some(shell.take_jobs());
+        }
+        if let Some(helper) = rl.helper_mut().filter(|_| plain_reader) {
+            helper.continuation_prompt = Some(shell.prompt("PS2"));
+        }
         let readline = rl.readline("$ ");
+        if let Some(jobs) = lent_jobs.and_then(|lent_jobs| lent_jobs.lock().unwrap().take()) {
+            shell.restore_jobs(jobs);
+        }
 
         match readline {
             Ok(input) => {
-                // Sanitize the input
+                // Typed text is valid UTF-8, but some characters are carried like raw bytes in the shell
+                let input = encoding::decode(input.as_bytes());
+                // Sanitize the input, which may span several lines
                 let trimmed_input = input.trim(); // Trim also removes the \n when pressing enter to run the command
                 if trimmed_input.is_empty() {
                     continue;
                 }
 
-                // Clean the input
-                let parsed_input = parse_input(trimmed_input);
-                let parsed_command = parsed_input[0];
-                let mut parsed_args: Vec<&str> = if parsed_input.len() > 2 {
-                    parsed_input[2..].into() // The second element in the parsed_input is " ", hence skip it
-                } else {
-                    Vec::new()
-                };
-
-                let stdout_path = get_stdout_stream_path(&mut parsed_args);
-                let stderr_path = get_stderr_stream_path(&mut parsed_args);
-                let mut shell = Shell::new(parsed_command, &parsed_args, stdout_path, stderr_path); // TODO: move creation of shell outside for loop
-
-                // println!("{:?} {}", parsed_args, parsed_args.len());
-
-                let status_code = shell.execute();
+                shell.run_source(trimmed_input);
 
                 // Quit the shell if user supplies the `exit` command
-                // TODO: create a getter for this
-                if shell.command == "exit" {
+                if let Some(status_code) = shell.exit_code() {
                     return Ok(status_code);
                 }
-
-                shell.write_to_stdout_buffer(); // Write the output of the command to the output buffer
-                shell.write_to_stderr_buffer(); // Write the error of the command to the error buffer
             }
             Err(ReadlineError::Interrupted) => {
                 // println!("CTRL-C");
-                return Ok(ExitCode::from(130));
+                return Ok(130);
             }
             Err(ReadlineError::Eof) => {
-                // println!("CTRL-D");
-                return Ok(ExitCode::from(130));
+                // The end of the input, like after CTRL-D or at the end of `-s` commands
+                // CTRL-D is ignored once when there are stopped jobs, like `exit`
+                if shell.may_exit() {
+                    return Ok(shell.last_status);
+                }
             }
             Err(err) => {
                 eprintln!("Error: {:?}", err);
-                return Ok(ExitCode::from(1));
+                return Ok(1);
             }
         }
     }
@@ -0,0 +1,824 @@
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    rc::Rc,
+};
+use thiserror::Error;
+
+use crate::lexer::{Lexer, Token};
+
+#[derive(Debug, Error)]
+pub enum ParseError {
+    #[error("syntax error near unexpected token `{0}'")]
+    UnexpectedToken(String),
+    #[error("syntax error: unexpected end of file")]
+    UnexpectedEof,
+    #[error("unexpected EOF while looking for matching `{0}'")]
+    Unterminated(char),
+    #[error("here-document delimited by end-of-file (wanted `{0}')")]
+    HereDocument(String),
+}
+
+impl ParseError {
+    // Whether the input ended in the middle of a command, so that more lines could complete it
+    pub fn is_incomplete(&self) -> bool {
+        matches!(
+            self,
+            ParseError::UnexpectedEof | ParseError::Unterminated(_) | ParseError::HereDocument(_)
+        )
+    }
+}
+
+// Words which only have a special meaning at the start of a command
+const RESERVED_WORDS: [&str; 17] = [
+    "if", "then", "else", "elif", "fi", "while", "until", "for", "in", "do", "done", "{", "}", "!",
+    "function", "[[", "]]",
+];
+
+// Operators of `[[ ]]` taking a single operand, like `-f file`
+pub const UNARY_OPERATORS: [&str; 24] = [
+    "-a", "-b", "-c", "-d", "-e", "-f", "-g", "-h", "-k", "-n", "-o", "-p", "-r", "-s", "-t", "-u",
+    "-v", "-w", "-x", "-z", "-G", "-L", "-N", "-O",
+];
+
+// Operators of `[[ ]]` between two operands, like `a == b`
+pub const BINARY_OPERATORS: [&str; 15] = [
+    "=", "==", "!=", "=~", "<", ">", "-eq", "-ne", "-lt", "-le", "-gt", "-ge", "-nt", "-ot", "-ef",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RedirectOp {
+    Input,     // <
+    Output,    // > and >|
+    Append,    // >>
+    ReadWrite, // <>
+    DupInput,  // <&
+    DupOutput, // >&
+    OutputAll, // &>
+    AppendAll, // &>>
+    // `<<` and `<<-`, the target of which is the body of the here-document
+    HereDoc,
+    // A here-document with a quoted delimiter, whose body is not expanded
+    QuotedHereDoc,
+}
+
+#[derive(Debug)]
+pub struct Redirect {
+    pub fd: Option<u32>,
+    pub op: RedirectOp,
+    pub target: String,
+}
+
+#[derive(Debug, Default)]
+pub struct SimpleCommand {
+    pub assignments: Vec<String>,
+    pub words: Vec<String>,
+    pub redirects: Vec<Redirect>,
+}
+
+#[derive(Debug)]
+pub enum CompoundCommand {
+    BraceGroup(List),
+    Subshell(List),
+    If {
+        // Each `if`/`elif` condition with the body run when it succeeds
+        branches: Vec<(List, List)>,
+        else_body: Option<List>,
+    },
+    While {
+        condition: List,
+        body: List,
+        until: bool,
+    },
+    For {
+        variable: String,
+        // `None` when the `in` part is missing, meaning loop over "$@"
+        words: Option<Vec<String>>,
+        body: List,
+    },
+    // `(( expr ))`, with the expression not yet expanded
+    Arithmetic(String),
+    // `[[ expr ]]`
+    Conditional(ConditionalExpression),
+}
+
+// The expression inside `[[ ]]`, whose words are expanded when it is evaluated
+#[derive(Debug)]
+pub enum ConditionalExpression {
+    // A lone word, true when it is not empty
+    Word(String),
+    Unary(String, String),
+    Binary(String, String, String),
+    Not(Box<ConditionalExpression>),
+    And(Box<ConditionalExpression>, Box<ConditionalExpression>),
+    Or(Box<ConditionalExpression>, Box<ConditionalExpression>),
+}
+
+#[derive(Debug)]
+pub struct FunctionDefinition {
+    pub name: String,
+    pub body: Command,
+    // Text of the definition as the user wrote it, used by `type`
+    pub source: String,
+}
+
+#[derive(Debug)]
+pub enum Command {
+    Simple(SimpleCommand),
+    Compound(CompoundCommand, Vec<Redirect>),
+    // Shared with the shell's function table once the definition runs
+    FunctionDefinition(Rc<FunctionDefinition>),
+}
+
+#[derive(Debug)]
+pub struct Pipeline {
+    pub negated: bool,
+    pub commands: Vec<Command>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AndOrOp {
+    And,
+    Or,
+}
+
+#[derive(Debug)]
+pub struct AndOr {
+    pub first: Pipeline,
+    pub rest: Vec<(AndOrOp, Pipeline)>,
+    // Whether it ends with `&`, which runs it as a job in the background
+    pub background: bool,
+    // The text it was parsed from, shown for its job
+    pub source: String,
+}
+
+pub type List = Vec<AndOr>;
+
+// Check if the string can be used as a variable or function name
+pub fn is_valid_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => {}
+        _ => return false,
+    }
+    chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+// Split `name[subscript]` into the name and the subscript
+pub fn split_subscript(name: &str) -> Option<(&str, &str)> {
+    let (base, rest) = name.split_once('[')?;
+    let subscript = rest.strip_suffix(']')?;
+    is_valid_name(base).then_some((base, subscript))
+}
+
+// Length of the target of an assignment like `name=value`, `name+=value` or `name[subscript]=value`
+fn assignment_target_len(word: &str) -> Option<usize> {
+    let mut len = word
+        .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+        .unwrap_or(word.len());
+    if !is_valid_name(&word[..len]) {
+        return None;
+    }
+    if word[len..].starts_with('[') {
+        len += word[len..].find(']')? + 1;
+    }
+    (word[len..].starts_with('=') || word[len..].starts_with("+=")).then_some(len)
+}
+
+// Check if the word looks like `name=value`
+pub fn is_assignment(word: &str) -> bool {
+    assignment_target_len(word).is_some()
+}
+
+// Split an assignment into its target, whether it appends with `+=`, and the value
+pub fn split_assignment(word: &str) -> Option<(&str, bool, &str)> {
+    let len = assignment_target_len(word)?;
+    match word[len..].strip_prefix("+=") {
+        Some(value) => Some((&word[..len], true, value)),
+        None => Some((&word[..len], false, &word[len + 1..])),
+    }
+}
+
+// Check if the input stops before the end of a command, like after `if true` or in a quote
+pub fn is_incomplete(input: &str) -> bool {
+    let mut parser = Parser::new(input);
+    loop {
+        match parser.next_complete_command(&HashMap::new()) {
+            Ok(Some(_)) => continue,
+            Ok(None) => return false,
+            Err(error) => return error.is_incomplete(),
+        }
+    }
+}
+
+// Remove the quotes from the delimiter of a here-document, also telling if it had any
+fn here_document_delimiter(word: &str) -> (String, bool) {
+    let mut delimiter = String::new();
+    let mut chars = word.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' | '"' => {}
+            '\\' => delimiter.extend(chars.next()),
+            _ => delimiter.push(c),
+        }
+    }
+    let quoted = word.contains(['\'', '"', '\\']);
+    (delimiter, quoted)
+}
+
+// A token with its byte span in the input
+// The flag is set on the last token of an alias whose value ends with a blank,
+// which makes the word after it subject to alias expansion too
+type SpannedToken = (Token, usize, usize, bool);
+
+pub struct Parser<'a> {
+    lexer: Lexer<'a>,
+    // A token which has been looked at but not consumed yet
+    peeked: Option<SpannedToken>,
+    // Tokens coming from expanded aliases, read before the rest of the input
+    pending: VecDeque<SpannedToken>,
+    aliases: HashMap<String, String>,
+    // Whether the next word should be checked for an alias even if it is not the command name
+    alias_next_word: bool,
+    // End offset of the last consumed token
+    last_end: usize,
+}
+
+impl<'a> Parser<'a> {
+    pub fn new(input: &'a str) -> Self {
+        Self {
+            lexer: Lexer::new(input),
+            peeked: None,
+            pending: VecDeque::new(),
+            aliases: HashMap::new(),
+            alias_next_word: false,
+            last_end: 0,
+        }
+    }
+
+    // Without comments `#` is an ordinary character
+    pub fn set_comments(&mut self, comments: bool) {
+        self.lexer.set_comments(comments);
+    }
+
+    fn peek(&mut self) -> Result<&Token, ParseError> {
+        if self.peeked.is_none() {
+            self.peeked = match self.pending.pop_front() {
+                Some(token) => Some(token),
+                None => {
+                    let (token, start, end) = self.lexer.next_token()?;
+                    Some((token, start, end, false))
+                }
+            };
+        }
+        Ok(&self.peeked.as_ref().unwrap().0)
+    }
+
+    // Byte offset where the next token starts
+    fn peek_start(&mut self) -> Result<usize, ParseError> {
+        self.peek()?;
+        Ok(self.peeked.as_ref().unwrap().1)
+    }
+
+    fn next(&mut self) -> Result<Token, ParseError> {
+        self.peek()?;
+        let (token, _, end, alias_blank) = self.peeked.take().unwrap();
+        self.last_end = end;
+        self.alias_next_word = alias_blank;
+        Ok(token)
+    }
+
+    // Replace the next word with the value of the alias it names, as many times as needed
+    // An alias is not expanded again inside its own value, so `alias ls='ls -F'` works
+    fn expand_aliases(&mut self) -> Result<(), ParseError> {
+        let mut expanded = HashSet::new();
+        loop {
+            self.peek()?;
+            let value = match &self.peeked.as_ref().unwrap().0 {
+                Token::Word(word) if !expanded.contains(word) => match self.aliases.get(word) {
+                    Some(value) => value.clone(),
+                    None => return Ok(()),
+                },
+                _ => return Ok(()),
+            };
+            let (token, start, end, _) = self.peeked.take().unwrap();
+            if let Token::Word(word) = token {
+                expanded.insert(word);
+            }
+
+            // The tokens of the value take the place of the alias name in the input
+            let mut lexer = Lexer::new(&value);
+            let mut tokens = Vec::new();
+            loop {
+                match lexer.next_token()?.0 {
+                    Token::Eof => break,
+                    token => tokens.push((token, start, end, false)),
+                }
+            }
+            if value.ends_with([' ', '\t']) {
+                match tokens.last_mut() {
+                    Some(last) => last.3 = true,
+                    None => self.alias_next_word = true,
+                }
+            }
+            for token in tokens.into_iter().rev() {
+                self.pending.push_front(token);
+            }
+        }
+    }
+
+    fn peek_is_op(&mut self, op: &str) -> Result<bool, ParseError> {
+        Ok(matches!(self.peek()?, Token::Op(x) if *x == op))
+    }
+
+    // Check if the next token is the given reserved word
+    fn peek_is_reserved(&mut self, word: &str) -> Result<bool, ParseError> {
+        Ok(matches!(self.peek()?, Token::Word(x) if x == word))
+    }
+
+    fn unexpected(token: Token) -> ParseError {
+        match token {
+            Token::Word(word) => ParseError::UnexpectedToken(word),
+            Token::IoNumber(fd) => ParseError::UnexpectedToken(fd.to_string()),
+            Token::Op(op) => ParseError::UnexpectedToken(op.to_owned()),
+            Token::Arithmetic(_) => ParseError::UnexpectedToken("((".to_owned()),
+            Token::Newline => ParseError::UnexpectedToken("newline".to_owned()),
+            Token::Eof => ParseError::UnexpectedEof,
+        }
+    }
+
+    fn expect_reserved(&mut self, word: &str) -> Result<(), ParseError> {
+        if self.peek_is_reserved(word)? {
+            self.next()?;
+            Ok(())
+        } else {
+            Err(Parser::unexpected(self.next()?))
+        }
+    }
+
+    fn skip_newlines(&mut self) -> Result<(), ParseError> {
+        while self.peek()? == &Token::Newline {
+            self.next()?;
+        }
+        Ok(())
+    }
+
+    // Parse the next line worth of commands, returns `None` once the input is exhausted
+    // Commands are parsed one line at a time so that a definition can be used on the lines after it
+    pub fn next_complete_command(
+        &mut self,
+        aliases: &HashMap<String, String>,
+    ) -> Result<Option<List>, ParseError> {
+        // Aliases defined by a command only apply from the next line on
+        self.aliases = aliases.clone();
+        self.skip_newlines()?;
+        if self.peek()? == &Token::Eof {
+            return Ok(None);
+        }
+
+        let mut list = Vec::new();
+        loop {
+            list.push(self.parse_and_or()?);
+            match self.next()? {
+                Token::Op(op @ (";" | "&")) => {
+                    list.last_mut().unwrap().background = op == "&";
+                    if matches!(self.peek()?, Token::Newline | Token::Eof) {
+                        break;
+                    }
+                }
+                Token::Newline | Token::Eof => break,
+                token => return Err(Parser::unexpected(token)),
+            }
+        }
+        Ok(Some(list))
+    }
+
+    // Parse a list inside a compound command, stopping before any of the `terminators`
+    fn parse_compound_list(&mut self, terminators: &[&str]) -> Result<List, ParseError> {
+        let mut list = Vec::new();
+        loop {
+            self.skip_newlines()?;
+            let at_end = match self.peek()? {
+                Token::Word(word) => terminators.contains(&word.as_str()),
+                Token::Op(")") => terminators.contains(&")"),
+                Token::Eof => return Err(ParseError::UnexpectedEof),
+                _ => false,
+            };
+            if at_end {
+                break;
+            }
+
+            list.push(self.parse_and_or()?);
+            match self.peek()? {
+                Token::Op(";") | Token::Newline => {
+                    self.next()?;
+                }
+                Token::Op("&") => {
+                    self.next()?;
+                    list.last_mut().unwrap().background = true;
+                }
+                Token::Word(word) if terminators.contains(&word.as_str()) => {}
+                Token::Op(")") if terminators.contains(&")") => {}
+                _ => return Err(Parser::unexpected(self.next()?)),
+            }
+        }
+
+        if list.is_empty() {
+            return Err(Parser::unexpected(self.next()?));
+        }
+        Ok(list)
+    }
+
+    fn parse_and_or(&mut self) -> Result<AndOr, ParseError> {
+        let start = self.peek_start()?;
+        let first = self.parse_pipeline()?;
+        let mut rest = Vec::new();
+        loop {
+            let op = if self.peek_is_op("&&")? {
+                AndOrOp::And
+            } else if self.peek_is_op("||")? {
+                AndOrOp::Or
+            } else {
+                break;
+            };
+            self.next()?;
+            self.skip_newlines()?;
+            rest.push((op, self.parse_pipeline()?));
+        }
+        Ok(AndOr {
+            first,
+            rest,
+            background: false,
+            source: self.lexer.input()[start..self.last_end].to_owned(),
+        })
+    }
+
+    fn parse_pipeline(&mut self) -> Result<Pipeline, ParseError> {
+        let negated = self.peek_is_reserved("!")?;
+        if negated {
+            self.next()?;
+        }
+
+        let mut commands = vec![self.parse_command()?];
+        while self.peek_is_op("|")? {
+            self.next()?;
+            self.skip_newlines()?;
+            commands.push(self.parse_command()?);
+        }
+        Ok(Pipeline { negated, commands })
+    }
+
+    fn parse_command(&mut self) -> Result<Command, ParseError> {
+        self.expand_aliases()?;
+        let start = self.peek_start()?;
+        let compound = match self.peek()? {
+            Token::Op("(") => {
+                self.next()?;
+                let list = self.parse_compound_list(&[")"])?;
+                self.next()?; // The `)`
+                Some(CompoundCommand::Subshell(list))
+            }
+            Token::Arithmetic(expression) => {
+                let expression = expression.clone();
+                self.next()?;
+                Some(CompoundCommand::Arithmetic(expression))
+            }
+            Token::Word(word) => match word.as_str() {
+                "{" | "if" | "while" | "until" | "for" | "[[" => {
+                    Some(self.parse_compound_command()?)
+                }
+                "function" => return self.parse_function_definition(start),
+                "}" | "then" | "else" | "elif" | "fi" | "do" | "done" | "in" => {
+                    return Err(Parser::unexpected(self.next()?))
+                }
+                _ => None,
+            },
+            _ => None,
+        };
+
+        match compound {
+            Some(compound) => {
+                let redirects = self.parse_redirects()?;
+                Ok(Command::Compound(compound, redirects))
+            }
+            None => self.parse_simple_command(start),
+        }
+    }
+
+    // Parse one of the compound commands which start with a reserved word
+    fn parse_compound_command(&mut self) -> Result<CompoundCommand, ParseError> {
+        let keyword = match self.next()? {
+            Token::Word(word) => word,
+            token => return Err(Parser::unexpected(token)),
+        };
+
+        match keyword.as_str() {
+            "{" => {
+                let list = self.parse_compound_list(&["}"])?;
+                self.expect_reserved("}")?;
+                Ok(CompoundCommand::BraceGroup(list))
+            }
+            "if" => {
+                let mut branches = Vec::new();
+                let mut else_body = None;
+                loop {
+                    let condition = self.parse_compound_list(&["then"])?;
+                    self.expect_reserved("then")?;
+                    let body = self.parse_compound_list(&["elif", "else", "fi"])?;
+                    branches.push((condition, body));
+
+                    if self.peek_is_reserved("elif")? {
+                        self.next()?;
+                        continue;
+                    }
+                    if self.peek_is_reserved("else")? {
+                        self.next()?;
+                        else_body = Some(self.parse_compound_list(&["fi"])?);
+                    }
+                    self.expect_reserved("fi")?;
+                    break;
+                }
+                Ok(CompoundCommand::If {
+                    branches,
+                    else_body,
+                })
+            }
+            "while" | "until" => {
+                let condition = self.parse_compound_list(&["do"])?;
+                let body = self.parse_do_group()?;
+                Ok(CompoundCommand::While {
+                    condition,
+                    body,
+                    until: keyword == "until",
+                })
+            }
+            "for" => {
+                let variable = match self.next()? {
+                    Token::Word(word) if is_valid_name(&word) => word,
+                    token => return Err(Parser::unexpected(token)),
+                };
+
+                // Either `for x in a b c; do`, `for x; do` or `for x do`
+                let mut words = None;
+                self.skip_newlines()?;
+                if self.peek_is_reserved("in")? {
+                    self.next()?;
+                    let mut list = Vec::new();
+                    while let Token::Word(_) = self.peek()? {
+                        if let Token::Word(word) = self.next()? {
+                            list.push(word);
+                        }
+                    }
+                    words = Some(list);
+                }
+                if self.peek_is_op(";")? || self.peek()? == &Token::Newline {
+                    self.next()?;
+                }
+
+                let body = self.parse_do_group()?;
+                Ok(CompoundCommand::For {
+                    variable,
+                    words,
+                    body,
+                })
+            }
+            "[[" => {
+                let expression = self.parse_conditional_or()?;
+                self.expect_reserved("]]")?;
+                Ok(CompoundCommand::Conditional(expression))
+            }
+            _ => Err(ParseError::UnexpectedToken(keyword)),
+        }
+    }
+
+    // The expressions of `[[ ]]`, from the loosest binding `||` to single tests
+    fn parse_conditional_or(&mut self) -> Result<ConditionalExpression, ParseError> {
+        let mut expression = self.parse_conditional_and()?;
+        while self.peek_is_op("||")? {
+            self.next()?;
+            self.skip_newlines()?;
+            let right = self.parse_conditional_and()?;
+            expression = ConditionalExpression::Or(Box::new(expression), Box::new(right));
+        }
+        Ok(expression)
+    }
+
+    fn parse_conditional_and(&mut self) -> Result<ConditionalExpression, ParseError> {
+        let mut expression = self.parse_conditional_not()?;
+        while self.peek_is_op("&&")? {
+            self.next()?;
+            self.skip_newlines()?;
+            let right = self.parse_conditional_not()?;
+            expression = ConditionalExpression::And(Box::new(expression), Box::new(right));
+        }
+        Ok(expression)
+    }
+
+    fn parse_conditional_not(&mut self) -> Result<ConditionalExpression, ParseError> {
+        self.skip_newlines()?;
+        if self.peek_is_reserved("!")? {
+            self.next()?;
+            let expression = self.parse_conditional_not()?;
+            return Ok(ConditionalExpression::Not(Box::new(expression)));
+        }
+        self.parse_conditional_primary()
+    }
+
+    fn parse_conditional_primary(&mut self) -> Result<ConditionalExpression, ParseError> {
+        let word = match self.next()? {
+            Token::Op("(") => {
+                let expression = self.parse_conditional_or()?;
+                self.skip_newlines()?;
+                match self.next()? {
+                    Token::Op(")") => return Ok(expression),
+                    token => return Err(Parser::unexpected(token)),
+                }
+            }
+            Token::Word(word) if word != "]]" => word,
+            token => return Err(Parser::unexpected(token)),
+        };
+
+        if UNARY_OPERATORS.contains(&word.as_str()) {
+            if let Token::Word(operand) = self.peek()? {
+                if operand != "]]" {
+                    let operand = operand.clone();
+                    self.next()?;
+                    return Ok(ConditionalExpression::Unary(word, operand));
+                }
+            }
+        }
+
+        let operator = match self.peek()? {
+            Token::Word(op) if BINARY_OPERATORS.contains(&op.as_str()) => op.clone(),
+            Token::Op(op @ ("<" | ">")) => op.to_string(),
+            _ => return Ok(ConditionalExpression::Word(word)),
+        };
+        self.next()?;
+
+        // Characters like `(` and `|` are part of a regular expression rather than operators
+        let right = if operator == "=~" && self.peeked.is_none() && self.pending.is_empty() {
+            let (right, _, end) = self.lexer.read_regex()?;
+            self.last_end = end;
+            right
+        } else {
+            self.next()?
+        };
+        match right {
+            Token::Word(right) if right != "]]" => {
+                Ok(ConditionalExpression::Binary(operator, word, right))
+            }
+            token => Err(Parser::unexpected(token)),
+        }
+    }
+
+    // Parse `do list done`
+    fn parse_do_group(&mut self) -> Result<List, ParseError> {
+        self.skip_newlines()?;
+        self.expect_reserved("do")?;
+        let body = self.parse_compound_list(&["done"])?;
+        self.expect_reserved("done")?;
+        Ok(body)
+    }
+
+    // Parse `function name [()] compound-command`
+    fn parse_function_definition(&mut self, start: usize) -> Result<Command, ParseError> {
+        self.next()?; // The `function` keyword
+        let name = match self.next()? {
+            Token::Word(word) if !RESERVED_WORDS.contains(&word.as_str()) => word,
+            token => return Err(Parser::unexpected(token)),
+        };
+        if self.peek_is_op("(")? {
+            self.next()?;
+            if !self.peek_is_op(")")? {
+                return Err(Parser::unexpected(self.next()?));
+            }
+            self.next()?;
+        }
+        self.parse_function_body(name, start)
+    }
+
+    // Parse the compound command making up a function body
+    fn parse_function_body(&mut self, name: String, start: usize) -> Result<Command, ParseError> {
+        self.skip_newlines()?;
+        let body = match self.peek()? {
+            Token::Op("(") | Token::Arithmetic(_) => self.parse_command()?,
+            Token::Word(word)
+                if ["{", "if", "while", "until", "for", "[["].contains(&word.as_str()) =>
+            {
+                self.parse_command()?
+            }
+            _ => return Err(Parser::unexpected(self.next()?)),
+        };
+        Ok(Command::FunctionDefinition(Rc::new(FunctionDefinition {
+            name,
+            body,
+            source: self.lexer.input()[start..self.last_end].to_owned(),
+        })))
+    }
+
+    fn parse_redirects(&mut self) -> Result<Vec<Redirect>, ParseError> {
+        let mut redirects = Vec::new();
+        while let Some(redirect) = self.parse_redirect()? {
+            redirects.push(redirect);
+        }
+        Ok(redirects)
+    }
+
+    // Parse a redirection like `2>> file` if the next token starts one
+    fn parse_redirect(&mut self) -> Result<Option<Redirect>, ParseError> {
+        let fd = match self.peek()? {
+            Token::IoNumber(fd) => {
+                let fd = *fd;
+                self.next()?;
+                Some(fd)
+            }
+            _ => None,
+        };
+
+        let op = match self.peek()? {
+            Token::Op("<") => RedirectOp::Input,
+            Token::Op(">") | Token::Op(">|") => RedirectOp::Output,
+            Token::Op(">>") => RedirectOp::Append,
+            Token::Op("<>") => RedirectOp::ReadWrite,
+            Token::Op("<&") => RedirectOp::DupInput,
+            Token::Op(">&") => RedirectOp::DupOutput,
+            Token::Op("&>") => RedirectOp::OutputAll,
+            Token::Op("&>>") => RedirectOp::AppendAll,
+            Token::Op("<<") | Token::Op("<<-") => RedirectOp::HereDoc,
+            _ if fd.is_some() => return Err(Parser::unexpected(self.next()?)),
+            _ => return Ok(None),
+        };
+        let strip_tabs = self.next()? == Token::Op("<<-");
+
+        let target = match self.next()? {
+            Token::Word(target) => target,
+            token => return Err(Parser::unexpected(token)),
+        };
+        if op != RedirectOp::HereDoc {
+            return Ok(Some(Redirect { fd, op, target }));
+        }
+
+        let (delimiter, quoted) = here_document_delimiter(&target);
+        let body = self.lexer.read_here_document(&delimiter, strip_tabs)?;
+        let op = match quoted {
+            true => RedirectOp::QuotedHereDoc,
+            false => RedirectOp::HereDoc,
+        };
+        Ok(Some(Redirect {
+            fd,
+            op,
+            target: body,
+        }))
+    }
+
+    fn parse_simple_command(&mut self, start: usize) -> Result<Command, ParseError> {
+        let mut command = SimpleCommand::default();
+        loop {
+            if std::mem::take(&mut self.alias_next_word) {
+                self.expand_aliases()?;
+            }
+            if let Some(redirect) = self.parse_redirect()? {
+                command.redirects.push(redirect);
+                continue;
+            }
+
+            match self.peek()? {
+                Token::Word(word) if command.words.is_empty() && is_assignment(word) => {
+                    if let Token::Word(word) = self.next()? {
+                        command.assignments.push(word);
+                    }
+                }
+                Token::Word(_) => {
+                    if let Token::Word(word) = self.next()? {
+                        command.words.push(word);
+                    }
+                }
+                // `name() compound-command` defines a function
+                Token::Op("(")
+                    if command.words.len() == 1
+                        && command.assignments.is_empty()
+                        && command.redirects.is_empty() =>
+                {
+                    self.next()?;
+                    if !self.peek_is_op(")")? {
+                        return Err(Parser::unexpected(self.next()?));
+                    }
+                    self.next()?;
+                    let name = command.words.pop().unwrap();
+                    if RESERVED_WORDS.contains(&name.as_str()) {
+                        return Err(ParseError::UnexpectedToken(name));
+                    }
+                    return self.parse_function_body(name, start);
+                }
+                _ => break,
+            }
+        }
+
+        if command.words.is_empty()
+            && command.assignments.is_empty()
+            && command.redirects.is_empty()
+        {
+            return Err(Parser::unexpected(self.next()?));
+        }
+        Ok(Command::Simple(command))
+    }
+}
@@ -0,0 +1,231 @@
+// Glob pattern matching as used by `${var#pattern}` and friends, and pathname expansion
+// `*` matches any string, `?` any character and `[...]` a set of characters
+// A character escaped with a backslash in the pattern always matches itself
+use std::fs;
+use std::path::Path;
+
+use crate::encoding;
+
+// Check if the whole text matches the pattern
+pub fn pattern_matches(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+
+    let (mut p, mut t) = (0, 0);
+    // Position of the last `*` seen and of the text it is currently matched up to, to backtrack to
+    let mut star: Option<(usize, usize)> = None;
+    while t < text.len() {
+        if p < pattern.len() {
+            match pattern[p] {
+                '*' => {
+                    star = Some((p, t));
+                    p += 1;
+                    continue;
+                }
+                '?' => {
+                    p += 1;
+                    t += 1;
+                    continue;
+                }
+                '[' => {
+                    if let Some((matched, len)) = match_bracket(&pattern[p..], text[t]) {
+                        if matched {
+                            p += len;
+                            t += 1;
+                            continue;
+                        }
+                    } else if text[t] == '[' {
+                        // Without a closing `]` the `[` is an ordinary character
+                        p += 1;
+                        t += 1;
+                        continue;
+                    }
+                }
+                '\\' if p + 1 < pattern.len() => {
+                    if pattern[p + 1] == text[t] {
+                        p += 2;
+                        t += 1;
+                        continue;
+                    }
+                }
+                c => {
+                    if c == text[t] {
+                        p += 1;
+                        t += 1;
+                        continue;
+                    }
+                }
+            }
+        }
+
+        // Mismatch, let the last `*` swallow one more character
+        match star {
+            Some((star_p, star_t)) => {
+                p = star_p + 1;
+                t = star_t + 1;
+                star = Some((star_p, star_t + 1));
+            }
+            None => return false,
+        }
+    }
+
+    while p < pattern.len() && pattern[p] == '*' {
+        p += 1;
+    }
+    p == pattern.len()
+}
+
+// Match a character against a bracket expression like `[a-z]` at the start of the pattern
+// Returns whether it matched and the length of the expression, or `None` if there is no closing `]`
+fn match_bracket(pattern: &[char], c: char) -> Option<(bool, usize)> {
+    let mut i = 1;
+    let negated = matches!(pattern.get(i), Some('!') | Some('^'));
+    if negated {
+        i += 1;
+    }
+
+    let mut matched = false;
+    let mut first = true;
+    loop {
+        let current = *pattern.get(i)?;
+        if current == ']' && !first {
+            return Some((matched != negated, i + 1));
+        }
+        first = false;
+
+        // Character classes like `[:digit:]`
+        if current == '[' && pattern.get(i + 1) == Some(&':') {
+            let rest: String = pattern[i + 2..].iter().collect();
+            if let Some(end) = rest.find(":]") {
+                matched |= class_matches(&rest[..end], c);
+                i += 2 + rest[..end].chars().count() + 2;
+                continue;
+            }
+        }
+
+        let low = if current == '\\' {
+            i += 1;
+            *pattern.get(i)?
+        } else {
+            current
+        };
+        i += 1;
+
+        // A range like `a-z`, where a `-` right before the `]` is an ordinary character
+        if pattern.get(i) == Some(&'-') && pattern.get(i + 1).is_some_and(|&x| x != ']') {
+            let mut high = pattern[i + 1];
+            i += 2;
+            if high == '\\' {
+                high = *pattern.get(i)?;
+                i += 1;
+            }
+            matched |= low <= c && c <= high;
+        } else {
+            matched |= low == c;
+        }
+    }
+}
+
+fn class_matches(class: &str, c: char) -> bool {
+    match class {
+        "alnum" => c.is_alphanumeric(),
+        "alpha" => c.is_alphabetic(),
+        "blank" => c == ' ' || c == '\t',
+        "cntrl" => c.is_control(),
+        "digit" => c.is_ascii_digit(),
+        "graph" => !c.is_control() && !c.is_whitespace(),
+        "lower" => c.is_lowercase(),
+        "print" => !c.is_control(),
+        "punct" => c.is_ascii_punctuation(),
+        "space" => c.is_whitespace(),
+        "upper" => c.is_uppercase(),
+        "xdigit" => c.is_ascii_hexdigit(),
+        _ => false,
+    }
+}
+
+// Escape the characters which have a special meaning in patterns
+pub fn escape_pattern(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(c, '*' | '?' | '[' | ']' | '\\') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+// Remove the backslashes escaping characters of the pattern
+fn unescape_pattern(pattern: &str) -> String {
+    let mut text = String::with_capacity(pattern.len());
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => text.extend(chars.next()),
+            _ => text.push(c),
+        }
+    }
+    text
+}
+
+// Whether the pattern has a `*`, `?` or `[...]` which is not escaped
+pub fn has_wildcards(pattern: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let mut i = 0;
+    while i < pattern.len() {
+        match pattern[i] {
+            '\\' => i += 1,
+            '*' | '?' => return true,
+            '[' if match_bracket(&pattern[i..], ' ').is_some() => return true,
+            _ => {}
+        }
+        i += 1;
+    }
+    false
+}
+
+// The sorted paths of the existing files matching the pattern, one `/` separated component at a time
+// Names starting with a `.` are only matched by a pattern which starts with a `.` as well
+pub fn expand_pathname(pattern: &str) -> Vec<String> {
+    let (mut paths, pattern) = match pattern.strip_prefix('/') {
+        Some(rest) => (vec!["/".to_owned()], rest),
+        None => (vec![String::new()], pattern),
+    };
+    let components: Vec<&str> = pattern.split('/').collect();
+    for (i, component) in components.iter().enumerate() {
+        let separator = if i + 1 < components.len() { "/" } else { "" };
+        let mut next = Vec::new();
+        for path in paths {
+            if !has_wildcards(component) {
+                next.push(format!(
+                    "{}{}{}",
+                    path,
+                    unescape_pattern(component),
+                    separator
+                ));
+                continue;
+            }
+            let directory = if path.is_empty() { "." } else { path.as_str() };
+            let Ok(entries) = fs::read_dir(encoding::to_os_string(directory)) else {
+                continue;
+            };
+            let hidden = component.starts_with('.') || component.starts_with("\\.");
+            for entry in entries.flatten() {
+                let name = encoding::from_os_str(&entry.file_name());
+                if (hidden || !name.starts_with('.')) && pattern_matches(component, &name) {
+                    next.push(format!("{}{}{}", path, name, separator));
+                }
+            }
+        }
+        paths = next;
+    }
+
+    paths.retain(|path| {
+        Path::new(&encoding::to_os_string(path))
+            .symlink_metadata()
+            .is_ok()
+    });
+    paths.sort();
+    paths
+}
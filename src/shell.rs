@@ -1,179 +1,2496 @@
 use std::{
-    io::{BufRead, Write},
-    os::unix::fs::PermissionsExt,
+    collections::{BTreeMap, HashMap},
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    os::{
+        fd::{AsRawFd, OwnedFd, RawFd},
+        unix::{
+            fs::PermissionsExt,
+            process::{CommandExt, ExitStatusExt},
+        },
+    },
     path::Path,
-    process::{Command, ExitCode, Stdio},
+    process::Command as ExternalCommand,
+    rc::Rc,
 };
 
-pub struct Shell<'a> {
-    pub command: &'a str,
-    args: &'a Vec<&'a str>,
+use crate::arith::ArithmeticError;
+use crate::assign::AssignedValue;
+use crate::encoding;
+use crate::expand::ExpandError;
+use crate::jobs::{JobState, Jobs};
+use crate::parser::{
+    is_valid_name, split_assignment, split_subscript, AndOr, AndOrOp, Command, CompoundCommand,
+    FunctionDefinition, List, Parser, Pipeline, Redirect, RedirectOp, SimpleCommand,
+};
+use crate::signal;
+use crate::sys::{self, ForkResult};
+use crate::variables::{Value, Variable, Variables};
+
+// Command implemented in the current program are called builtin commands
+// NOTE: If adding new builtin command, make sure to add it below and in `execute_builtin`
+const BUILTIN_COMMANDS: [&str; 32] = [
+    ":", "true", "false", "exit", "echo", "type", "pwd", "cd", "export", "unset", "local",
+    "return", "command", "builtin", "break", "continue", "alias", "unalias", "let", "test", "[",
+    "declare", "typeset", "shopt", "source", ".", "set", "trap", "jobs", "wait", "kill", "disown",
+];
+
+// Builtins whose assignment arguments, like `local a=(x y)`, are expanded like assignments
+const DECLARATION_COMMANDS: [&str; 3] = ["declare", "typeset", "local"];
+
+// Options of `shopt` along with their default values
+const SHELL_OPTIONS: [(&str, bool); 2] = [("huponexit", false), ("interactive_comments", true)];
+
+// Options of `set` along with the letter which also sets them, all of them are off by default
+const SET_OPTIONS: [(&str, Option<char>); 8] = [
+    ("allexport", Some('a')),
+    ("errexit", Some('e')),
+    ("noglob", Some('f')),
+    ("notify", Some('b')),
+    ("nounset", Some('u')),
+    ("pipefail", None),
+    ("posix", None),
+    ("xtrace", Some('x')),
+];
+
+// The option of `set` for a letter like `e` in `set -e`
+pub fn option_name(letter: char) -> Option<&'static str> {
+    SET_OPTIONS
+        .iter()
+        .find(|(_, c)| *c == Some(letter))
+        .map(|(name, _)| *name)
+}
+
+// Alias names can not contain characters which have a meaning to the shell
+fn is_valid_alias_name(name: &str) -> bool {
+    !name.is_empty()
+        && !name.contains(|c: char| c.is_whitespace() || "/$`=\\'\"<>&|;()".contains(c))
+}
+
+// Whether the string reads back as itself without any quoting
+fn is_plain_word(value: &str) -> bool {
+    !value.is_empty()
+        && !value.starts_with('#')
+        && value
+            .chars()
+            .all(|c| c.is_alphanumeric() || "_-+=./:,@%#".contains(c))
+}
+
+// Quote the word only if the shell would not read it back unchanged, as in the trace of `set -x`
+fn quote_if_needed(word: &str) -> String {
+    match is_plain_word(word) {
+        true => word.to_owned(),
+        false => single_quote(word),
+    }
+}
+
+// The words as a command which the shell reads back as the same words
+fn quote_words(words: &[String]) -> String {
+    let words: Vec<String> = words.iter().map(|word| quote_if_needed(word)).collect();
+    words.join(" ")
+}
+
+// Quote the string with single quotes so that the shell reads it back unchanged
+pub fn single_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', r"'\''"))
+}
+
+// Quote the string with double quotes, escaping the characters which are special inside them
+fn double_quote(value: &str) -> String {
+    let mut quoted = String::from('"');
+    for c in value.chars() {
+        if matches!(c, '"' | '\\' | '$' | '`') {
+            quoted.push('\\');
+        }
+        quoted.push(c);
+    }
+    quoted.push('"');
+    quoted
+}
+
+// The `declare` command which recreates the variable, as printed by `declare -p`
+fn declaration(name: &str, variable: &Variable) -> String {
+    let mut flags = String::new();
+    match variable.value {
+        Some(Value::Indexed(_)) => flags.push('a'),
+        Some(Value::Associative(_)) => flags.push('A'),
+        _ => {}
+    }
+    if variable.exported {
+        flags.push('x');
+    }
+    if flags.is_empty() {
+        flags.push('-');
+    }
+
+    let value = match &variable.value {
+        None => String::new(),
+        Some(Value::Scalar(value)) => format!("={}", double_quote(value)),
+        Some(array) => {
+            let elements: Vec<String> = array
+                .entries()
+                .iter()
+                .map(|(key, value)| format!("[{}]={}", key, double_quote(value)))
+                .collect();
+            // Like bash, associative arrays are printed with a space before the `)`
+            let end = match array {
+                Value::Associative(_) => " ",
+                _ => "",
+            };
+            format!("=({}{})", elements.join(" "), end)
+        }
+    };
+    format!("declare -{} {}{}", flags, name, value)
+}
+
+// Pending change of control flow, checked after every command
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Jump {
+    Break(usize),
+    Continue(usize),
+    Return,
+    Exit,
+}
+
+// What a trap is set on, a signal or an event of the shell itself
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum TrapCondition {
+    Exit,
+    Signal(libc::c_int),
+    Debug,
+    Err,
+    Return,
+}
+
+impl TrapCondition {
+    // Parse a condition like `EXIT`, `INT`, `SIGINT` or `2`
+    fn parse(spec: &str) -> Option<TrapCondition> {
+        match spec.to_ascii_uppercase().as_str() {
+            "EXIT" | "0" => Some(TrapCondition::Exit),
+            "DEBUG" => Some(TrapCondition::Debug),
+            "ERR" => Some(TrapCondition::Err),
+            "RETURN" => Some(TrapCondition::Return),
+            _ => signal::number(spec).map(TrapCondition::Signal),
+        }
+    }
+
+    fn name(&self) -> String {
+        match self {
+            TrapCondition::Exit => "EXIT".to_owned(),
+            TrapCondition::Signal(number) => format!("SIG{}", signal::name(*number).unwrap_or("")),
+            TrapCondition::Debug => "DEBUG".to_owned(),
+            TrapCondition::Err => "ERR".to_owned(),
+            TrapCondition::Return => "RETURN".to_owned(),
+        }
+    }
+}
+
+// The action of a trap along with the depth of function calls it was set at
+// The DEBUG, ERR and RETURN traps do not run in the functions called from there
+struct Trap {
+    action: String,
+    function_depth: usize,
+}
+
+// A file descriptor replaced by a redirection along with a copy of the original to restore afterwards
+// The copy is `None` if the file descriptor was not open before
+struct SavedFd {
+    fd: RawFd,
+    original: Option<OwnedFd>,
+}
+
+pub struct Shell {
     stdout_buffer: String,
-    stdout_stream: Box<dyn Write>,
     stderr_buffer: String,
-    stderr_stream: Box<dyn Write>,
+    pub variables: Variables,
+    pub positional: Vec<String>,
+    functions: HashMap<String, Rc<FunctionDefinition>>,
+    aliases: HashMap<String, String>,
+    pub last_status: u8,
+    jump: Option<Jump>,
+    // Number of function calls, loops and sourced files currently running,
+    // for `return`, `break` and `continue`
+    function_depth: usize,
+    loop_depth: usize,
+    source_depth: usize,
+    // The last invalid value of `BASH_XTRACEFD` which was reported
+    invalid_trace_fd: Option<String>,
+    // Number of conditions currently running, like of an `if` or on the left of `&&`,
+    // where a failing command does not make `errexit` exit the shell
+    condition_depth: usize,
+    pub shell_name: String,
+    pub pid: u32,
+    // Whether the commands are typed by the user rather than read from a script
+    pub interactive: bool,
+    // `c` when running the commands of `-c`, `s` when reading them from the input, shown in `$-`
+    pub input_flag: Option<char>,
+    // Options set with `shopt`
+    shell_options: BTreeMap<&'static str, bool>,
+    // Options set with `set`
+    set_options: BTreeMap<&'static str, bool>,
+    traps: BTreeMap<TrapCondition, Trap>,
+    // Set while the action of a trap runs, during which no other trap runs
+    running_trap: bool,
+    // Signals ignored when the shell started, which a shell running a script can not trap
+    ignored_on_entry: Vec<libc::c_int>,
+    jobs: Jobs,
+    // Set in a forked child about to run its last command, where an external command then
+    // replaces the child process instead of running in a new one, so that it gets the pid of the child
+    exec_external: bool,
+    // Set once a child process running in the foreground finished
+    waited_in_foreground: bool,
+    // Set when exiting was refused because of stopped jobs, until the next command
+    exit_warned: bool,
 }
 
-impl<'a> Shell<'a> {
-    pub fn new(
-        command: &'a str,
-        args: &'a Vec<&'a str>,
-        stdout_stream: Box<dyn Write>,
-        stderr_stream: Box<dyn Write>,
-    ) -> Self {
+impl Shell {
+    pub fn new() -> Self {
+        let shell_name = std::env::args_os()
+            .next()
+            .map(|name| encoding::from_os_str(&name))
+            .unwrap_or_else(|| "codecrafters-shell".to_owned());
+        let mut variables = Variables::from_env();
+        for (name, default) in [("PS2", "> "), ("PS4", "+ ")] {
+            if variables.get(name).is_none() {
+                variables.set(name, default);
+            }
+        }
+        // Rust ignores SIGPIPE in every program, so it can not tell whether it was ignored before
+        let ignored_on_entry: Vec<libc::c_int> = signal::SIGNALS
+            .iter()
+            .map(|(_, number)| *number)
+            .filter(|&number| number != libc::SIGPIPE && signal::is_ignored(number))
+            .collect();
+        let traps = ignored_on_entry
+            .iter()
+            .map(|&number| {
+                let trap = Trap {
+                    action: String::new(),
+                    function_depth: 0,
+                };
+                (TrapCondition::Signal(number), trap)
+            })
+            .collect();
+        // Finished jobs are noticed at the next safe point after SIGCHLD
+        let _ = signal::catch(libc::SIGCHLD);
         Self {
-            command,
-            args,
             stdout_buffer: String::new(),
-            stdout_stream,
             stderr_buffer: String::new(),
-            stderr_stream,
+            variables,
+            positional: Vec::new(),
+            functions: HashMap::new(),
+            aliases: HashMap::new(),
+            last_status: 0,
+            jump: None,
+            function_depth: 0,
+            loop_depth: 0,
+            source_depth: 0,
+            invalid_trace_fd: None,
+            condition_depth: 0,
+            shell_name,
+            pid: std::process::id(),
+            interactive: true,
+            input_flag: None,
+            shell_options: BTreeMap::from(SHELL_OPTIONS),
+            set_options: SET_OPTIONS.iter().map(|(name, _)| (*name, false)).collect(),
+            traps,
+            running_trap: false,
+            ignored_on_entry,
+            jobs: Jobs::default(),
+            exec_external: false,
+            waited_in_foreground: false,
+            exit_warned: false,
         }
     }
 
+    // Exit code requested by the `exit` command, `None` if the shell should keep running
+    pub fn exit_code(&self) -> Option<u8> {
+        match self.jump {
+            Some(Jump::Exit) => Some(self.last_status),
+            _ => None,
+        }
+    }
+
+    // Whether the option of `set` is on
+    pub fn option(&self, name: &str) -> bool {
+        self.set_options.get(name).copied().unwrap_or(false)
+    }
+
+    // Turn an option of `set` on or off, returning `false` if there is no option with this name
+    pub fn set_option(&mut self, name: &str, on: bool) -> bool {
+        match self.set_options.get_mut(name) {
+            Some(value) => {
+                *value = on;
+                if name == "allexport" {
+                    self.variables.export_all = on;
+                }
+                true
+            }
+            None => false,
+        }
+    }
+
+    // The letters of the options which are on, for `$-`
+    pub fn option_flags(&self) -> String {
+        let mut flags: Vec<char> = SET_OPTIONS
+            .iter()
+            .filter(|(name, _)| self.set_options[name])
+            .filter_map(|(_, letter)| *letter)
+            .collect();
+        if self.interactive {
+            flags.push('i');
+        }
+        flags.sort_unstable();
+        flags.extend(self.input_flag);
+        flags.into_iter().collect()
+    }
+
+    // The pid of the last job started in the background, for `$!`
+    pub fn last_background_pid(&self) -> Option<libc::pid_t> {
+        self.jobs.last_pid
+    }
+
     // Parse error code from the Optional str (will be None if the exit command was not given any exit code)
-    // Set default of 0 status code if received no status code
-    // If unable to parse the string of status code to u8 then give 1 status code
-    fn parse_exit_code(&self) -> u8 {
-        self.args.first().unwrap_or(&"0").parse::<u8>().unwrap_or(1)
+    // Without a status code the status of the last command is used
+    // The status code wraps around like in other shells, and one which is not a number gives 2
+    fn parse_exit_code(&mut self, args: &[String]) -> u8 {
+        match args.get(1) {
+            None => self.last_status,
+            Some(arg) => match arg.parse::<i64>() {
+                Ok(status) => status as u8,
+                Err(_) => {
+                    self.stderr_buffer += &format!("exit: {}: numeric argument required\n", arg);
+                    2
+                }
+            },
+        }
+    }
+
+    // Find a file to source in the PATH variable, which unlike a command does not have to be executable
+    fn find_source_file(&self, name: &str) -> Option<String> {
+        let path_directories = self.variables.get("PATH")?;
+        path_directories.split(':').find_map(|directory| {
+            let path = format!("{}/{}", directory, name);
+            Path::new(&encoding::to_os_string(&path))
+                .is_file()
+                .then_some(path)
+        })
     }
 
-    // Return absolute path of the command if found in the PATH environment variable
-    fn get_absolute_command_path(command_name: &str) -> Option<String> {
-        let path_directories = std::env::var("PATH").unwrap();
+    // Return absolute path of the command if found in the PATH variable
+    fn get_absolute_command_path(&self, command_name: &str) -> Option<String> {
+        let path_directories = self.variables.get("PATH")?;
         for directory in path_directories.split(":") {
             // Check if directory actually exists in the filesystem
-            let directory_entries = match std::fs::read_dir(directory) {
+            let directory_entries = match std::fs::read_dir(encoding::to_os_string(directory)) {
                 Ok(x) => x,
                 Err(_) => continue,
             };
-            for file in directory_entries {
+            for file in directory_entries.flatten() {
                 // Check permission bits to find executables
-                let is_executable = file
-                    .as_ref()
-                    .unwrap()
-                    .metadata()
-                    .unwrap()
-                    .permissions()
-                    .mode()
-                    & 0o111
-                    != 0;
-                let file_name = file.as_ref().unwrap().file_name().into_string().unwrap();
-                if is_executable && file_name == command_name {
-                    return Some(file.unwrap().path().to_str().unwrap().to_owned());
+                let is_executable = match file.metadata() {
+                    Ok(metadata) => metadata.permissions().mode() & 0o111 != 0,
+                    Err(_) => false,
+                };
+                if is_executable && encoding::from_os_str(&file.file_name()) == command_name {
+                    return Some(encoding::from_os_str(file.path().as_os_str()));
                 }
             }
         }
         None
     }
 
-    // Print the vector of strings to stdout
-    // This function assumes that the `parsed_args` is already in a clean state
-    // like removing duplicate spaces, handling quotes, etc.
-    fn builtin_echo(&mut self) -> ExitCode {
-        for arg in self.args {
-            self.stdout_buffer += arg;
+    // Add an error message to the error buffer and write it out right away
+    pub fn print_error(&mut self, message: &str) {
+        self.stderr_buffer += message;
+        self.stderr_buffer += "\n";
+        self.write_to_stderr_buffer();
+    }
+
+    // The expanded value of a prompt variable like `PS2`
+    pub fn prompt(&mut self, name: &str) -> String {
+        let prompt = self.variables.get(name).unwrap_or_default().to_owned();
+        self.expand_word_to_string(&prompt).unwrap_or(prompt)
+    }
+
+    // Print a command about to run for `set -x` after the expanded `PS4`, to the file descriptor
+    // in `BASH_XTRACEFD` or else stderr
+    fn print_trace(&mut self, command: &str) {
+        let line = self.prompt("PS4") + command;
+
+        self.write_to_stderr_buffer();
+        let trace_fd = self
+            .variables
+            .get("BASH_XTRACEFD")
+            .filter(|fd| !fd.is_empty())
+            .map(|fd| fd.to_owned());
+        if let Some(fd) = trace_fd {
+            let bytes = encoding::encode(&(line.clone() + "\n"));
+            if fd.parse().is_ok_and(|fd| sys::write_fd(fd, &bytes).is_ok()) {
+                return;
+            }
+            // An invalid value is only reported once, the traces then go to stderr
+            if self.invalid_trace_fd.as_ref() != Some(&fd) {
+                self.print_error(&format!(
+                    "BASH_XTRACEFD: {}: invalid value for trace file descriptor",
+                    fd
+                ));
+                self.invalid_trace_fd = Some(fd);
+            }
+        }
+        self.print_error(&line);
+    }
+
+    // Report a failed expansion, returning the status of the command
+    // An unset parameter with `set -u` or `${var:?}`, or an invalid arithmetic expression, also makes
+    // a shell which is not interactive exit
+    pub fn expansion_failed(&mut self, error: ExpandError) -> u8 {
+        self.print_error(&error.to_string());
+        match error.exit_status().filter(|_| !self.interactive) {
+            Some(status) => {
+                self.last_status = status;
+                self.jump = Some(Jump::Exit);
+                status
+            }
+            None => 1,
+        }
+    }
+
+    // Report an invalid expression of `(( ))` or of an integer comparison in `[[ ]]`, which only makes
+    // the command fail, unlike an arithmetic expansion
+    pub fn arithmetic_failed(&mut self, error: ExpandError) -> u8 {
+        match error {
+            ExpandError::Arithmetic(error) if !matches!(error, ArithmeticError::Unbound(_)) => {
+                self.print_error(&error.to_string());
+                1
+            }
+            error => self.expansion_failed(error),
         }
+    }
+
+    // Print the vector of strings to stdout separated by spaces
+    fn builtin_echo(&mut self, args: &[String]) -> u8 {
+        self.stdout_buffer += &args[1..].join(" ");
         self.stdout_buffer += "\n";
-        ExitCode::SUCCESS
+        0
     }
 
     // Print the current working directory
-    fn builtin_pwd(&mut self) -> ExitCode {
+    fn builtin_pwd(&mut self) -> u8 {
         let current_dir = std::env::current_dir().unwrap();
-        self.stdout_buffer += current_dir.to_str().unwrap();
+        self.stdout_buffer += &encoding::from_os_str(current_dir.as_os_str());
         self.stdout_buffer += "\n";
-        ExitCode::SUCCESS
+        0
     }
 
     // Change the directory
-    fn builtin_cd(&mut self) -> ExitCode {
-        // TODO: Maybe try `Cow` to avoid using String
-        let corrected_path = if self.args.first().unwrap_or(&"~") == &"~" {
-            std::env::var("HOME").unwrap()
-        } else {
-            (*self.args.first().unwrap()).to_owned()
+    fn builtin_cd(&mut self, args: &[String]) -> u8 {
+        let corrected_path = match args.get(1).map(|x| x.as_str()) {
+            None | Some("~") => self.variables.get("HOME").unwrap_or("/").to_owned(),
+            Some(path) => path.to_owned(),
         };
 
-        let path_obj = Path::new(corrected_path.as_str());
+        let path_obj = encoding::to_os_string(&corrected_path);
 
         // Changes the current directory
         // if error occurs, like no directory exists, then, print an error
         if std::env::set_current_dir(path_obj).is_err() {
             self.stdout_buffer += &format!("cd: {}: No such file or directory\n", corrected_path);
-            return ExitCode::from(1); // Missing directory error code
+            return 1; // Missing directory error code
         }
-        ExitCode::SUCCESS
+        0
     }
 
     // The `type` command
-    fn builtin_type(&mut self) -> ExitCode {
-        // Command implemented in the current program are called builtin commands
-        // NOTE: If adding new builtin command, make sure to add it below
-        // TODO: use enums for this to reduce human errors
-        let builtin_commands = ["exit", "echo", "type", "pwd", "cd"];
-
-        let first_arg = self.args.first().unwrap();
-
-        if builtin_commands.contains(first_arg) {
-            self.stdout_buffer += &format!("{} is a shell builtin\n", first_arg);
-        } else if let Some(command_path) = Shell::get_absolute_command_path(first_arg) {
-            self.stdout_buffer += &format!("{} is {}\n", first_arg, command_path)
-        } else {
-            self.stdout_buffer += &format!("{}: not found\n", first_arg);
-            return ExitCode::from(1);
+    fn builtin_type(&mut self, args: &[String]) -> u8 {
+        let mut status = 0;
+        for name in &args[1..] {
+            if let Some(value) = self.aliases.get(name) {
+                self.stdout_buffer += &format!("{} is aliased to `{}'\n", name, value);
+            } else if let Some(function) = self.functions.get(name) {
+                self.stdout_buffer += &format!("{} is a function\n{}\n", name, function.source);
+            } else if BUILTIN_COMMANDS.contains(&name.as_str()) {
+                self.stdout_buffer += &format!("{} is a shell builtin\n", name);
+            } else if let Some(command_path) = self.get_absolute_command_path(name) {
+                self.stdout_buffer += &format!("{} is {}\n", name, command_path)
+            } else {
+                self.stdout_buffer += &format!("{}: not found\n", name);
+                status = 1;
+            }
         }
-        ExitCode::SUCCESS
+        status
     }
 
-    // Write the output buffer to the output stream
-    pub fn write_to_stdout_buffer(&mut self) {
-        self.stdout_stream.write_all(self.stdout_buffer.as_bytes());
+    // Define aliases with `name=value`, or print them in a form which can be read back
+    fn builtin_alias(&mut self, args: &[String]) -> u8 {
+        if args.len() == 1 {
+            let mut aliases: Vec<(&String, &String)> = self.aliases.iter().collect();
+            aliases.sort();
+            for (name, value) in aliases {
+                self.stdout_buffer += &format!("alias {}={}\n", name, single_quote(value));
+            }
+            return 0;
+        }
+
+        let mut status = 0;
+        for arg in &args[1..] {
+            match arg.split_once('=') {
+                Some((name, _)) if !is_valid_alias_name(name) => {
+                    self.stderr_buffer += &format!("alias: `{}': invalid alias name\n", name);
+                    status = 1;
+                }
+                Some((name, value)) => {
+                    self.aliases.insert(name.to_owned(), value.to_owned());
+                }
+                None => match self.aliases.get(arg) {
+                    Some(value) => {
+                        self.stdout_buffer += &format!("alias {}={}\n", arg, single_quote(value));
+                    }
+                    None => {
+                        self.stderr_buffer += &format!("alias: {}: not found\n", arg);
+                        status = 1;
+                    }
+                },
+            }
+        }
+        status
     }
 
-    // Write the error buffer to the error stream
-    pub fn write_to_stderr_buffer(&mut self) {
-        self.stderr_stream.write_all(self.stderr_buffer.as_bytes());
+    // Remove aliases, or all of them with `-a`
+    fn builtin_unalias(&mut self, args: &[String]) -> u8 {
+        if args.get(1).map(|x| x.as_str()) == Some("-a") {
+            self.aliases.clear();
+            return 0;
+        }
+        if args.len() == 1 {
+            self.stderr_buffer += "unalias: usage: unalias [-a] name [name ...]\n";
+            return 2;
+        }
+
+        let mut status = 0;
+        for name in &args[1..] {
+            if self.aliases.remove(name).is_none() {
+                self.stderr_buffer += &format!("unalias: {}: not found\n", name);
+                status = 1;
+            }
+        }
+        status
     }
 
-    // Execute the command with args and return appropriate status code
-    pub fn execute(&mut self) -> ExitCode {
-        match self.command {
-            "exit" => ExitCode::from(self.parse_exit_code()),
-            "echo" => self.builtin_echo(),
-            "pwd" => self.builtin_pwd(),
-            "cd" => self.builtin_cd(),
-            "type" => self.builtin_type(),
-            _ => {
-                // Run the command in a subshell if found in PATH
-                if Shell::get_absolute_command_path(self.command).is_some() {
-                    let child = Command::new(self.command)
-                        .args(self.args.iter().filter(|&x| !x.trim().is_empty()))
-                        .stdout(Stdio::piped())
-                        .stderr(Stdio::piped())
-                        .spawn()
-                        .expect("Command failed to run");
+    // Evaluate each argument as an arithmetic expression, succeeding when the last one is not zero
+    fn builtin_let(&mut self, args: &[String]) -> u8 {
+        if args.len() == 1 {
+            self.stderr_buffer += "let: expression expected\n";
+            return 1;
+        }
+
+        let mut value = 0;
+        for expression in &args[1..] {
+            match self.evaluate_arithmetic(expression) {
+                Ok(result) => value = result,
+                Err(error) => {
+                    self.stderr_buffer += &format!("let: {}\n", error);
+                    return 1;
+                }
+            }
+        }
+        (value == 0) as u8
+    }
+
+    // `test expr` and `[ expr ]`, with status 2 when the expression is invalid
+    fn builtin_test(&mut self, args: &[String]) -> u8 {
+        let name = &args[0];
+        let mut operands = &args[1..];
+        if name == "[" {
+            match operands.split_last() {
+                Some((last, rest)) if last == "]" => operands = rest,
+                _ => {
+                    self.stderr_buffer += "[: missing `]'\n";
+                    return 2;
+                }
+            }
+        }
+
+        match self.evaluate_test(operands) {
+            Ok(true) => 0,
+            Ok(false) => 1,
+            Err(error) => {
+                self.stderr_buffer += &format!("{}: {}\n", name, error);
+                2
+            }
+        }
+    }
+
+    // Mark variables for export to child processes, optionally assigning them
+    fn builtin_export(&mut self, args: &[String]) -> u8 {
+        if args.len() == 1 {
+            let mut exported: Vec<(&str, &str)> = self.variables.exported().into_iter().collect();
+            exported.sort();
+            for (name, value) in exported {
+                self.stdout_buffer += &format!("declare -x {}=\"{}\"\n", name, value);
+            }
+            return 0;
+        }
+
+        let mut status = 0;
+        for arg in &args[1..] {
+            let (name, value) = match arg.split_once('=') {
+                Some((name, value)) => (name, Some(value)),
+                None => (arg.as_str(), None),
+            };
+            if !is_valid_name(name) {
+                self.stderr_buffer += &format!("export: `{}': not a valid identifier\n", arg);
+                status = 1;
+                continue;
+            }
+            if let Some(value) = value {
+                self.variables.set(name, value);
+            }
+            self.variables.export(name);
+        }
+        status
+    }
+
+    // Remove variables, or functions with `-f`
+    fn builtin_unset(&mut self, args: &[String]) -> u8 {
+        let mut status = 0;
+        let mut functions = false;
+        for arg in &args[1..] {
+            match arg.as_str() {
+                "-f" => functions = true,
+                "-v" => functions = false,
+                name if functions => {
+                    self.functions.remove(name);
+                }
+                name => match split_subscript(name) {
+                    Some((name, "@" | "*")) => self.variables.unset(name),
+                    Some((name, subscript)) => match self.array_key(name, subscript) {
+                        Ok(key) => self.variables.unset_element(name, &key),
+                        Err(error) => {
+                            self.stderr_buffer += &format!("unset: {}\n", error);
+                            status = 1;
+                        }
+                    },
+                    None => self.variables.unset(name),
+                },
+            }
+        }
+        status
+    }
+
+    // Run the commands of a file in the current shell, with the arguments as positional parameters if any
+    // A name without a slash is looked up in PATH first, then in the current directory
+    fn builtin_source(&mut self, args: &[String]) -> u8 {
+        let name = match args.get(1) {
+            Some(name) => name,
+            None => {
+                self.stderr_buffer += &format!(
+                    "{0}: filename argument required\n{0}: usage: {0} filename [arguments]\n",
+                    args[0]
+                );
+                return 2;
+            }
+        };
+        let path = match name.contains('/') {
+            true => None,
+            false => self.find_source_file(name),
+        }
+        .unwrap_or_else(|| name.clone());
+
+        if Path::new(&encoding::to_os_string(&path)).is_dir() {
+            self.stderr_buffer += &format!("{}: {}: is a directory\n", args[0], name);
+            return 1;
+        }
+
+        let saved_positional = match args.len() > 2 {
+            true => Some(std::mem::replace(&mut self.positional, args[2..].to_vec())),
+            false => None,
+        };
+        let status = match self.source_file(&path) {
+            Ok(status) => status,
+            Err(error) => {
+                self.stderr_buffer += &format!("{}: {}\n", name, sys::error_message(&error));
+                1
+            }
+        };
+        if let Some(saved_positional) = saved_positional {
+            self.positional = saved_positional;
+        }
+        status
+    }
+
+    // Set options with `-s`, unset them with `-u`, or print them, as `shopt` commands with `-p`
+    // `-q` prints nothing, the status tells whether all the options are set
+    fn builtin_shopt(&mut self, args: &[String]) -> u8 {
+        let mut flags = String::new();
+        let mut names = &args[1..];
+        while let Some(options) = names.first().and_then(|arg| arg.strip_prefix('-')) {
+            if let Some(c) = options.chars().find(|c| !"pqsu".contains(*c)) {
+                self.stderr_buffer += &format!(
+                    "shopt: -{}: invalid option\nshopt: usage: shopt [-pqsu] [optname ...]\n",
+                    c
+                );
+                return 2;
+            }
+            flags += options;
+            names = &names[1..];
+        }
+        if flags.contains('s') && flags.contains('u') {
+            self.stderr_buffer += "shopt: cannot set and unset shell options simultaneously\n";
+            return 1;
+        }
+
+        let mut status = 0;
+        for name in names {
+            if !self.shell_options.contains_key(name.as_str()) {
+                self.stderr_buffer += &format!("shopt: {}: invalid shell option name\n", name);
+                status = 1;
+            }
+        }
+        let listing = names.is_empty();
+        let names: Vec<&'static str> = match listing {
+            true => self.shell_options.keys().copied().collect(),
+            false => SHELL_OPTIONS
+                .iter()
+                .map(|(name, _)| *name)
+                .filter(|name| names.iter().any(|x| x == name))
+                .collect(),
+        };
+
+        let set = flags.contains('s');
+        if (set || flags.contains('u')) && !listing {
+            for name in names {
+                self.shell_options.insert(name, set);
+            }
+            return status;
+        }
+
+        for name in names {
+            let value = self.shell_options[name];
+            // `shopt -s` and `shopt -u` alone only list the options with that value
+            if (set && !value) || (flags.contains('u') && value) {
+                continue;
+            }
+            if !value {
+                status = 1;
+            }
+            if flags.contains('q') {
+                continue;
+            }
+            match flags.contains('p') {
+                true => {
+                    let flag = if value { 's' } else { 'u' };
+                    self.stdout_buffer += &format!("shopt -{} {}\n", flag, name);
+                }
+                false => {
+                    let value = if value { "on" } else { "off" };
+                    self.stdout_buffer += &format!("{:<15}\t{}\n", name, value);
+                }
+            }
+        }
+        status
+    }
 
-                    let output = child.wait_with_output().expect("failed to read stdout");
+    // Turn options on with `-e` or `-o errexit` and off with `+e` or `+o errexit`,
+    // then set the positional parameters to the other arguments
+    // Without arguments the variables are printed, `-o` and `+o` alone print the options
+    fn builtin_set(&mut self, args: &[String]) -> u8 {
+        if args.len() == 1 {
+            self.print_set_variables();
+            return 0;
+        }
+        let mut args = args[1..].iter();
+        let mut positional = None;
+        while let Some(arg) = args.as_slice().first() {
+            let on = match arg.chars().next() {
+                Some('-') => true,
+                Some('+') => false,
+                _ => break,
+            };
+            args.next();
+            if arg == "--" {
+                positional = Some(Vec::new());
+                break;
+            }
+            // `set -` ends the options and turns off tracing, like `+x`
+            if arg == "-" {
+                self.set_option("xtrace", false);
+                break;
+            }
 
-                    // Append Vec<u8> to `stdout_buffer` String
-                    // For stdout
-                    for line in output.stdout.lines() {
-                        self.stdout_buffer += line.unwrap().as_str();
-                        self.stdout_buffer += "\n";
+            for c in arg[1..].chars() {
+                if c == 'o' {
+                    let Some(name) = args.next() else {
+                        self.print_set_options(on);
+                        continue;
+                    };
+                    if !self.set_option(name, on) {
+                        self.stderr_buffer += &format!("set: {}: invalid option name\n", name);
+                        return 2;
+                    }
+                    continue;
+                }
+                match option_name(c) {
+                    Some(name) => {
+                        self.set_option(name, on);
+                    }
+                    None => {
+                        self.stderr_buffer += &format!(
+                            "set: {}{}: invalid option\n\
+                             set: usage: set [-abefux] [-o option-name] [--] [-] [arg ...]\n",
+                            &arg[..1],
+                            c
+                        );
+                        return 2;
                     }
-                    // For stderr
-                    for line in output.stderr.lines() {
-                        self.stderr_buffer += line.unwrap().as_str();
-                        self.stderr_buffer += "\n";
+                }
+            }
+        }
+
+        let rest: Vec<String> = args.cloned().collect();
+        if !rest.is_empty() {
+            positional = Some(rest);
+        }
+        if let Some(positional) = positional {
+            self.positional = positional;
+        }
+        0
+    }
+
+    // Print the options of `set` with their values, or as the `set` commands which restore them
+    fn print_set_options(&mut self, on: bool) {
+        for (name, value) in &self.set_options {
+            self.stdout_buffer += &match (on, value) {
+                (true, true) => format!("{:<15}\ton\n", name),
+                (true, false) => format!("{:<15}\toff\n", name),
+                (false, true) => format!("set -o {}\n", name),
+                (false, false) => format!("set +o {}\n", name),
+            };
+        }
+    }
+
+    // Print the variables which have a value as assignments which the shell reads back
+    fn print_set_variables(&mut self) {
+        for name in self.variables.names() {
+            let Some(variable) = self.variables.get_variable(name) else {
+                continue;
+            };
+            let value = match &variable.value {
+                None => continue,
+                Some(Value::Scalar(value)) if is_plain_word(value) => value.clone(),
+                Some(Value::Scalar(value)) => single_quote(value),
+                Some(_) => {
+                    // Arrays are printed like by `declare -p`, after the flags
+                    let declaration = declaration(name, variable);
+                    declaration[declaration.find('=').unwrap_or(0) + 1..].to_owned()
+                }
+            };
+            self.stdout_buffer += &format!("{}={}\n", name, value);
+        }
+    }
+
+    // Run an action when a signal arrives, or on the EXIT, ERR, DEBUG and RETURN events of the shell
+    // An empty action ignores the signal and `-` or no action puts back the default, `-p` prints traps
+    fn builtin_trap(&mut self, args: &[String]) -> u8 {
+        let mut args = &args[1..];
+        let mut print = false;
+        while let Some(arg) = args.first() {
+            match arg.as_str() {
+                "-p" => print = true,
+                "-l" => {
+                    self.stdout_buffer += &signal::table();
+                    return 0;
+                }
+                "--" => {
+                    args = &args[1..];
+                    break;
+                }
+                option if option.starts_with('-') && option.len() > 1 => {
+                    self.stderr_buffer += &format!(
+                        "trap: {}: invalid option\ntrap: usage: trap [-lp] [[action] signal_spec ...]\n",
+                        option
+                    );
+                    return 2;
+                }
+                _ => break,
+            }
+            args = &args[1..];
+        }
+
+        let (action, specs) = match args {
+            [action, specs @ ..] if !print && !specs.is_empty() => (Some(action.as_str()), specs),
+            [_] if !print => (Some("-"), args),
+            _ => (None, args),
+        };
+        let mut status = 0;
+        let mut conditions = Vec::new();
+        for spec in specs {
+            match TrapCondition::parse(spec) {
+                Some(condition) => conditions.push(condition),
+                None => {
+                    self.stderr_buffer +=
+                        &format!("trap: {}: invalid signal specification\n", spec);
+                    status = 1;
+                }
+            }
+        }
+
+        let Some(action) = action else {
+            for (condition, trap) in &self.traps {
+                if specs.is_empty() || conditions.contains(condition) {
+                    self.stdout_buffer += &format!(
+                        "trap -- {} {}\n",
+                        single_quote(&trap.action),
+                        condition.name()
+                    );
+                }
+            }
+            return status;
+        };
+
+        for condition in conditions {
+            if let TrapCondition::Signal(number) = condition {
+                if !self.interactive && self.ignored_on_entry.contains(&number) {
+                    continue;
+                }
+                // Signals like KILL can not be caught, which is not reported
+                let _ = match action {
+                    "-" => signal::reset(number),
+                    "" => signal::ignore(number),
+                    _ => signal::catch(number),
+                };
+            }
+            match action {
+                "-" => {
+                    self.traps.remove(&condition);
+                }
+                _ => {
+                    let trap = Trap {
+                        action: action.to_owned(),
+                        function_depth: self.function_depth,
+                    };
+                    self.traps.insert(condition, trap);
+                }
+            }
+        }
+        status
+    }
+
+    // Run the action of the trap, if it is set and applies at this depth of function calls
+    // `$?` is left as it was unless the trap exits the shell
+    fn run_trap(&mut self, condition: TrapCondition) {
+        if self.running_trap || self.jump.is_some() {
+            return;
+        }
+        let action = match self.traps.get(&condition) {
+            Some(trap) if trap.action.is_empty() => return,
+            Some(trap)
+                if matches!(condition, TrapCondition::Signal(_))
+                    || self.function_depth <= trap.function_depth =>
+            {
+                trap.action.clone()
+            }
+            _ => return,
+        };
+        let status = self.last_status;
+        self.running_trap = true;
+        self.run_source(&action);
+        self.running_trap = false;
+        if self.exit_code().is_none() {
+            self.last_status = status;
+        }
+    }
+
+    // Run the traps of the signals which arrived, at a safe point between two commands
+    pub fn run_pending_traps(&mut self) {
+        if self.running_trap {
+            return;
+        }
+        for number in signal::take_pending() {
+            // Scripts and `set -b` announce the jobs as soon as possible, else it waits for the next prompt
+            if number == libc::SIGCHLD {
+                match !self.interactive || self.option("notify") {
+                    true => self.notify_jobs(),
+                    false => self.jobs.reap(),
+                }
+            }
+            self.run_trap(TrapCondition::Signal(number));
+        }
+    }
+
+    // End the shell with the status, after the trap on EXIT which can change it with `exit`
+    pub fn finish(&mut self, status: u8) -> u8 {
+        let Some(trap) = self.traps.remove(&TrapCondition::Exit) else {
+            return status;
+        };
+        self.jump = None;
+        self.last_status = status;
+        self.running_trap = true;
+        self.run_source(&trap.action);
+        self.exit_code().unwrap_or(status)
+    }
+
+    // A forked child keeps the ignored signals, but the other traps and the jobs belong to the parent shell
+    fn reset_child(&mut self) {
+        self.jobs.clear();
+        signal::take_pending();
+        for (condition, trap) in std::mem::take(&mut self.traps) {
+            if trap.action.is_empty() {
+                self.traps.insert(condition, trap);
+            } else if let TrapCondition::Signal(number) = condition {
+                let _ = signal::reset(number);
+            }
+        }
+    }
+
+    // List the jobs with their state, or only their pids with `-p`
+    // Jobs which terminated are forgotten once they were listed
+    fn builtin_jobs(&mut self, args: &[String]) -> u8 {
+        let mut args = &args[1..];
+        let (mut long, mut pids, mut running, mut stopped) = (false, false, false, false);
+        while let Some(arg) = args.first() {
+            match arg.as_str() {
+                "--" => {
+                    args = &args[1..];
+                    break;
+                }
+                option if option.starts_with('-') && option.len() > 1 => {
+                    for c in option[1..].chars() {
+                        match c {
+                            'l' => long = true,
+                            'p' => pids = true,
+                            'r' => running = true,
+                            's' => stopped = true,
+                            _ => {
+                                self.stderr_buffer += &format!(
+                                    "jobs: -{}: invalid option\njobs: usage: jobs [-lprs] [jobspec ...]\n",
+                                    c
+                                );
+                                return 2;
+                            }
+                        }
                     }
+                }
+                _ => break,
+            }
+            args = &args[1..];
+        }
+
+        self.jobs.reap();
+        let mut status = 0;
+        let mut ids = Vec::new();
+        for spec in args {
+            match self.jobs.find(spec) {
+                Ok(id) => ids.push(id),
+                Err(message) => {
+                    self.stderr_buffer += &format!("jobs: {}\n", message);
+                    status = 1;
+                }
+            }
+        }
+        if args.is_empty() {
+            ids = self.jobs.ids();
+        }
+
+        let mut listed = Vec::new();
+        for id in ids {
+            let Some(job) = self.jobs.get(id) else {
+                continue;
+            };
+            let is_stopped = matches!(job.state, JobState::Stopped(_));
+            if (running && job.state != JobState::Running) || (stopped && !is_stopped) {
+                continue;
+            }
+            self.stdout_buffer += &match pids {
+                true => format!("{}\n", job.pid),
+                false => format!("{}\n", self.jobs.format(job, long)),
+            };
+            listed.push(id);
+        }
+        // A job which stopped is not announced anymore once it was listed
+        for id in listed {
+            match self.jobs.get_mut(id) {
+                Some(job) if job.state.is_terminated() => {
+                    self.jobs.remove(id);
+                }
+                Some(job) => job.notified = true,
+                None => {}
+            }
+        }
+        status
+    }
 
-                    (output.status.code().unwrap() as u8).into() // Convert ExitStatus to ExitCode
-                } else {
-                    self.stdout_buffer += &format!("{}: command not found\n", self.command);
-                    ExitCode::from(127) // Command not found error code
+    // Wait for the jobs given by pid or job spec and return the status of the last one,
+    // or wait for all the jobs and return 0. With `-n` only one of them needs to finish
+    // A trapped signal interrupts the wait, which then returns 128 + the signal number
+    fn builtin_wait(&mut self, args: &[String]) -> u8 {
+        let mut args = &args[1..];
+        let (mut any, mut force) = (false, false);
+        while let Some(arg) = args.first() {
+            match arg.as_str() {
+                "--" => {
+                    args = &args[1..];
+                    break;
                 }
+                option if option.starts_with('-') && option.len() > 1 => {
+                    for c in option[1..].chars() {
+                        match c {
+                            'n' => any = true,
+                            'f' => force = true,
+                            _ => {
+                                self.stderr_buffer += &format!(
+                                    "wait: -{}: invalid option\nwait: usage: wait [-fn] [id ...]\n",
+                                    c
+                                );
+                                return 2;
+                            }
+                        }
+                    }
+                }
+                _ => break,
             }
+            args = &args[1..];
+        }
+
+        let mut status = 0;
+        let mut ids = Vec::new();
+        for arg in args {
+            let id = if arg.starts_with('%') {
+                self.jobs.find(arg)
+            } else if let Ok(pid) = arg.parse::<libc::pid_t>() {
+                match (self.jobs.with_pid(pid), self.jobs.finished_status(pid)) {
+                    (Some(id), _) => Ok(id),
+                    (None, Some(finished)) if !any => {
+                        status = finished;
+                        continue;
+                    }
+                    (None, _) if any => Err(format!("{}: no such job", pid)),
+                    (None, _) => Err(format!("pid {} is not a child of this shell", pid)),
+                }
+            } else {
+                self.stderr_buffer += &format!("wait: `{}': not a pid or valid job spec\n", arg);
+                status = 1;
+                continue;
+            };
+            match id {
+                Ok(id) if any => ids.push(id),
+                Ok(id) => match self.wait_for_jobs(&[id], true, force) {
+                    Ok(()) => status = self.take_job_status(id),
+                    Err(number) => return 128 + number as u8,
+                },
+                Err(message) => {
+                    self.stderr_buffer += &format!("wait: {}\n", message);
+                    status = 127;
+                }
+            }
+        }
+        if !any && !args.is_empty() {
+            return status;
+        }
+
+        if args.is_empty() {
+            ids = self.jobs.ids();
+        }
+        if any && ids.is_empty() {
+            return 127;
+        }
+        if let Err(number) = self.wait_for_jobs(&ids, !any, force) {
+            return 128 + number as u8;
+        }
+        if !any {
+            self.forget_jobs(self.jobs.terminated());
+            return 0;
+        }
+        // The first of the jobs which is done, in the order they were given
+        let done = ids.into_iter().find(|&id| {
+            self.jobs
+                .get(id)
+                .map_or(true, |job| job.state != JobState::Running)
+        });
+        done.map_or(127, |id| self.take_job_status(id))
+    }
+
+    // The status of a job which is done, forgetting the job once it terminated
+    fn take_job_status(&mut self, id: usize) -> u8 {
+        let Some(job) = self.jobs.get(id) else {
+            return 127;
+        };
+        let status = job.state.status();
+        if job.state.is_terminated() {
+            self.jobs.remove(id);
+        }
+        status
+    }
+
+    // Remove jobs from the table so that the shell forgets about them, or with `-h` keep them
+    // but never send them SIGHUP when the shell exits
+    // Without operands it applies to the current job, or with `-a` to every job and with `-r` to the running ones
+    fn builtin_disown(&mut self, args: &[String]) -> u8 {
+        let mut args = &args[1..];
+        let (mut keep, mut all, mut running) = (false, false, false);
+        while let Some(arg) = args.first() {
+            match arg.as_str() {
+                "--" => {
+                    args = &args[1..];
+                    break;
+                }
+                option if option.starts_with('-') && option.len() > 1 => {
+                    for c in option[1..].chars() {
+                        match c {
+                            'h' => keep = true,
+                            'a' => all = true,
+                            'r' => running = true,
+                            _ => {
+                                self.stderr_buffer += &format!(
+                                    "disown: -{}: invalid option\ndisown: usage: disown [-h] [-ar] [jobspec ... | pid ...]\n",
+                                    c
+                                );
+                                return 2;
+                            }
+                        }
+                    }
+                }
+                _ => break,
+            }
+            args = &args[1..];
+        }
+
+        self.jobs.reap();
+        let mut status = 0;
+        let mut ids = Vec::new();
+        if args.is_empty() && (all || running) {
+            ids = self.jobs.ids();
+        } else if args.is_empty() {
+            match self.jobs.find("%+") {
+                Ok(id) => ids.push(id),
+                Err(_) => {
+                    self.stderr_buffer += "disown: current: no such job\n";
+                    return 1;
+                }
+            }
+        }
+        for arg in args {
+            let id = match arg.parse::<libc::pid_t>() {
+                Ok(pid) => self
+                    .jobs
+                    .with_pid(pid)
+                    .ok_or(format!("{}: no such job", arg)),
+                Err(_) => self.jobs.find(arg),
+            };
+            match id {
+                Ok(id) => ids.push(id),
+                Err(message) => {
+                    self.stderr_buffer += &format!("disown: {}\n", message);
+                    status = 1;
+                }
+            }
+        }
+
+        for id in ids {
+            if running
+                && self
+                    .jobs
+                    .get(id)
+                    .is_some_and(|job| job.state != JobState::Running)
+            {
+                continue;
+            }
+            match keep {
+                true => {
+                    if let Some(job) = self.jobs.get_mut(id) {
+                        job.no_hangup = true;
+                    }
+                }
+                false => {
+                    self.jobs.remove(id);
+                }
+            }
+        }
+        status
+    }
+
+    // An interactive shell with stopped jobs only exits when asked twice in a row, after a warning
+    pub fn may_exit(&mut self) -> bool {
+        self.jobs.reap();
+        if !self.interactive || self.exit_warned || !self.jobs.any_stopped() {
+            return true;
+        }
+        self.print_error("There are stopped jobs.");
+        self.exit_warned = true;
+        false
+    }
+
+    // Send SIGHUP to the jobs when the shell exits, and SIGCONT to the stopped ones which would never get it
+    // otherwise. An interactive shell always hangs up its jobs, other shells only with `huponexit`
+    // Jobs marked with `disown -h` are left alone
+    pub fn hang_up_jobs(&mut self) {
+        if !self.interactive && !self.shell_options["huponexit"] {
+            return;
+        }
+        self.jobs.reap();
+        for id in self.jobs.ids() {
+            let job = self.jobs.get(id).unwrap();
+            if job.no_hangup || job.state.is_terminated() {
+                continue;
+            }
+            let _ = sys::kill(-job.pid, libc::SIGHUP);
+            if matches!(job.state, JobState::Stopped(_)) {
+                let _ = sys::kill(-job.pid, libc::SIGCONT);
+            }
+        }
+    }
+
+    // Send a signal, TERM unless given, to processes, to the process group of a job given by job spec,
+    // or to the process group `-pid` given by a negative pid
+    fn builtin_kill(&mut self, args: &[String]) -> u8 {
+        const USAGE: &str = "kill: usage: kill [-s sigspec | -n signum | -sigspec] pid | jobspec ... or kill -l [sigspec]\n";
+        let mut args = &args[1..];
+        let spec = match args.first().map(|arg| arg.as_str()) {
+            Some("-l" | "-L") => return self.list_signals(&args[1..]),
+            Some(option @ ("-s" | "-n")) => match args.get(1) {
+                Some(spec) => {
+                    args = &args[2..];
+                    Some(spec.as_str())
+                }
+                None => {
+                    self.stderr_buffer +=
+                        &format!("kill: {}: option requires an argument\n", option);
+                    return 1;
+                }
+            },
+            Some("--") => {
+                args = &args[1..];
+                None
+            }
+            Some(option) if option.starts_with('-') && option.len() > 1 => {
+                args = &args[1..];
+                Some(&option[1..])
+            }
+            _ => None,
+        };
+        let number = match spec {
+            // Signal 0 only checks that the process exists
+            Some("0") => 0,
+            Some(spec) => match signal::number(spec) {
+                Some(number) => number,
+                None => {
+                    self.stderr_buffer +=
+                        &format!("kill: {}: invalid signal specification\n", spec);
+                    return 1;
+                }
+            },
+            None => libc::SIGTERM,
+        };
+        if spec.is_some() && args.first().is_some_and(|arg| arg == "--") {
+            args = &args[1..];
+        }
+        if args.is_empty() {
+            self.stderr_buffer += USAGE;
+            return 2;
+        }
+
+        let mut status = 0;
+        for arg in args {
+            let result = if arg.starts_with('%') {
+                match self.jobs.find(arg) {
+                    Ok(id) => {
+                        let job = self.jobs.get(id).unwrap();
+                        let result = sys::kill(-job.pid, number);
+                        // A stopped job needs to continue to act on a signal which ends it
+                        if matches!(job.state, JobState::Stopped(_))
+                            && matches!(number, libc::SIGTERM | libc::SIGHUP)
+                        {
+                            let _ = sys::kill(-job.pid, libc::SIGCONT);
+                        }
+                        result
+                    }
+                    Err(message) => {
+                        self.stderr_buffer += &format!("kill: {}\n", message);
+                        status = 1;
+                        continue;
+                    }
+                }
+            } else if let Ok(pid) = arg.parse::<libc::pid_t>() {
+                sys::kill(pid, number)
+            } else {
+                self.stderr_buffer +=
+                    &format!("kill: {}: arguments must be process or job IDs\n", arg);
+                status = 1;
+                continue;
+            };
+            if let Err(error) = result {
+                self.stderr_buffer +=
+                    &format!("kill: ({}) - {}\n", arg, sys::error_message(&error));
+                status = 1;
+            }
+        }
+        status
+    }
+
+    // List the signals for `kill -l`, or the names of signals given by number or exit status
+    // and the numbers of signals given by name
+    fn list_signals(&mut self, specs: &[String]) -> u8 {
+        if specs.is_empty() {
+            self.stdout_buffer += &signal::table();
+            return 0;
+        }
+        let mut status = 0;
+        for spec in specs {
+            let translated = match spec.parse::<libc::c_int>() {
+                // A status above 128 is the one of a process killed by the signal
+                Ok(number) if number > 128 => {
+                    signal::name(number - 128).map(|name| name.to_owned())
+                }
+                Ok(number) => signal::name(number).map(|name| name.to_owned()),
+                Err(_) => signal::number(spec).map(|number| number.to_string()),
+            };
+            match translated {
+                Some(translated) => self.stdout_buffer += &format!("{}\n", translated),
+                None => {
+                    self.stderr_buffer +=
+                        &format!("kill: {}: invalid signal specification\n", spec);
+                    status = 1;
+                }
+            }
+        }
+        status
+    }
+
+    // Create variables visible only to the current function and the functions it calls
+    fn builtin_local(&mut self, args: &[String]) -> u8 {
+        if self.function_depth == 0 {
+            self.stderr_buffer += "local: can only be used in a function\n";
+            return 1;
+        }
+
+        self.declare_variables(args, true)
+    }
+
+    // Declare variables, which are local when used in a function
+    fn builtin_declare(&mut self, args: &[String]) -> u8 {
+        self.declare_variables(args, self.function_depth > 0)
+    }
+
+    // Shared by `declare` and `local`, with `-a` for indexed arrays, `-A` for associative arrays,
+    // `-x` to export and `-p` to print the declarations
+    fn declare_variables(&mut self, args: &[String], local: bool) -> u8 {
+        let builtin = &args[0];
+        let mut flags = String::new();
+        let mut names = &args[1..];
+        while let Some(options) = names.first().and_then(|arg| arg.strip_prefix('-')) {
+            if let Some(c) = options.chars().find(|c| !"aAxp".contains(*c)) {
+                self.stderr_buffer += &format!(
+                    "{0}: -{1}: invalid option\n{0}: usage: {0} [-aAxp] [name[=value] ...]\n",
+                    builtin, c
+                );
+                return 2;
+            }
+            flags += options;
+            names = &names[1..];
+        }
+
+        let mut status = 0;
+        if flags.contains('p') || (names.is_empty() && builtin != "local") {
+            let names = match names.is_empty() {
+                true => self
+                    .variables
+                    .names()
+                    .iter()
+                    .map(|x| x.to_string())
+                    .collect(),
+                false => names.to_vec(),
+            };
+            for name in names {
+                match self.variables.get_variable(&name) {
+                    Some(variable) => self.stdout_buffer += &(declaration(&name, variable) + "\n"),
+                    None => {
+                        self.stderr_buffer += &format!("{}: {}: not found\n", builtin, name);
+                        status = 1;
+                    }
+                }
+            }
+            return status;
+        }
+
+        for arg in names {
+            let (target, append, value) = match split_assignment(arg) {
+                Some((target, append, value)) => (target, append, Some(value)),
+                None => (arg.as_str(), false, None),
+            };
+            let name = split_subscript(target).map_or(target, |(name, _)| name);
+            if !is_valid_name(name) {
+                self.stderr_buffer += &format!("{}: `{}': not a valid identifier\n", builtin, arg);
+                status = 1;
+                continue;
+            }
+
+            if local && !self.variables.is_local(name) {
+                self.variables.set_local(name, None, false);
+            }
+            if let Err(message) = self.convert_variable(name, &flags) {
+                self.stderr_buffer += &format!("{}: {}: {}\n", builtin, name, message);
+                status = 1;
+                continue;
+            }
+            if let Some(value) = value {
+                let value = AssignedValue::Scalar(value.to_owned());
+                if let Err(error) = self.assign(target, append, value) {
+                    self.stderr_buffer += &format!("{}: {}\n", builtin, error);
+                    status = 1;
+                }
+            }
+            if flags.contains('x') {
+                self.variables.export(name);
+            }
+        }
+        status
+    }
+
+    // Turn the variable into an array for `declare -a` or `declare -A`, keeping its value
+    fn convert_variable(&mut self, name: &str, flags: &str) -> Result<(), &'static str> {
+        let converted = match self.variables.get_value(name) {
+            Some(Value::Associative(_)) if flags.contains('a') => {
+                return Err("cannot convert associative to indexed array");
+            }
+            Some(Value::Indexed(array)) if flags.contains('A') && !array.is_empty() => {
+                return Err("cannot convert indexed to associative array");
+            }
+            Some(Value::Scalar(value)) if flags.contains('A') => {
+                Value::Associative([("0".to_owned(), value.clone())].into())
+            }
+            Some(Value::Scalar(value)) if flags.contains('a') => {
+                Value::Indexed([(0, value.clone())].into())
+            }
+            None | Some(Value::Indexed(_)) if flags.contains('A') => {
+                Value::Associative(Default::default())
+            }
+            None if flags.contains('a') => Value::Indexed(Default::default()),
+            _ => return Ok(()),
+        };
+        self.variables.set_value(name, converted);
+        Ok(())
+    }
+
+    // Return from the current function with the given status, or the status of the last command
+    fn builtin_return(&mut self, args: &[String]) -> u8 {
+        if self.function_depth == 0 && self.source_depth == 0 {
+            self.stderr_buffer += "return: can only `return' from a function or sourced script\n";
+            return 1;
+        }
+
+        self.jump = Some(Jump::Return);
+        match args.get(1) {
+            None => self.last_status,
+            Some(arg) => match arg.parse::<i64>() {
+                Ok(status) => status as u8, // Wraps around like in other shells
+                Err(_) => {
+                    self.stderr_buffer += &format!("return: {}: numeric argument required\n", arg);
+                    2
+                }
+            },
+        }
+    }
+
+    // Leave (or skip to the next iteration of) the `n`th enclosing loop
+    fn builtin_break_continue(&mut self, args: &[String]) -> u8 {
+        if self.loop_depth == 0 {
+            self.stderr_buffer += &format!(
+                "{}: only meaningful in a `for', `while', or `until' loop\n",
+                args[0]
+            );
+            return 0;
+        }
+
+        let count = match args.get(1).map(|x| x.parse::<usize>()) {
+            None => 1,
+            Some(Ok(count)) if count > 0 => count.min(self.loop_depth),
+            Some(_) => {
+                self.stderr_buffer +=
+                    &format!("{}: {}: loop count out of range\n", args[0], args[1]);
+                return 1;
+            }
+        };
+        self.jump = Some(if args[0] == "break" {
+            Jump::Break(count)
+        } else {
+            Jump::Continue(count)
+        });
+        0
+    }
+
+    // Run a command skipping shell functions, or describe it with `-v`
+    fn builtin_command(&mut self, args: &[String]) -> u8 {
+        match args.get(1).map(|x| x.as_str()) {
+            None => 0,
+            Some("-v") => {
+                let mut status = 0;
+                for name in &args[2..] {
+                    if let Some(value) = self.aliases.get(name) {
+                        self.stdout_buffer += &format!("alias {}={}\n", name, single_quote(value));
+                    } else if self.functions.contains_key(name)
+                        || BUILTIN_COMMANDS.contains(&name.as_str())
+                    {
+                        self.stdout_buffer += &format!("{}\n", name);
+                    } else if let Some(command_path) = self.get_absolute_command_path(name) {
+                        self.stdout_buffer += &format!("{}\n", command_path);
+                    } else {
+                        status = 1;
+                    }
+                }
+                status
+            }
+            Some(_) => self.execute_builtin_or_external(&args[1..]),
+        }
+    }
+
+    // Run a builtin command skipping shell functions
+    fn builtin_builtin(&mut self, args: &[String]) -> u8 {
+        if args.len() == 1 {
+            return 0;
+        }
+        match self.execute_builtin(&args[1..]) {
+            Some(status) => status,
+            None => {
+                self.stderr_buffer += &format!("builtin: {}: not a shell builtin\n", args[1]);
+                1
+            }
+        }
+    }
+
+    // Write the output buffer to the output stream
+    pub fn write_to_stdout_buffer(&mut self) {
+        let mut stdout = io::stdout();
+        let _ = stdout.write_all(&encoding::encode(&self.stdout_buffer));
+        let _ = stdout.flush();
+        self.stdout_buffer.clear();
+    }
+
+    // Write the error buffer to the error stream
+    pub fn write_to_stderr_buffer(&mut self) {
+        let _ = io::stderr().write_all(&encoding::encode(&self.stderr_buffer));
+        self.stderr_buffer.clear();
+    }
+
+    // Run the commands of the file in the current shell, where `return` stops the file
+    pub fn source_file(&mut self, path: &str) -> io::Result<u8> {
+        let source = encoding::decode(&fs::read(encoding::to_os_string(path))?);
+        self.source_depth += 1;
+        let status = self.run_source(&source);
+        if self.jump == Some(Jump::Return) {
+            self.jump = None;
+        }
+        self.run_trap(TrapCondition::Return);
+        self.source_depth -= 1;
+        Ok(status)
+    }
+
+    // Run the script at `path` with `$0` set to it, returning its exit status
+    // Like other shells, the status is 127 when the script does not exist and 126 when it can not be read
+    pub fn run_script(&mut self, path: &str) -> u8 {
+        let source = match fs::read(encoding::to_os_string(path)) {
+            Ok(bytes) => encoding::decode(&bytes),
+            Err(error) => {
+                self.print_error(&format!("{}: {}", path, sys::error_message(&error)));
+                return match error.kind() {
+                    io::ErrorKind::NotFound => 127,
+                    _ => 126,
+                };
+            }
+        };
+        self.shell_name = path.to_owned();
+        self.run_source(&source)
+    }
+
+    // Parse and run the source one complete command at a time, returning the status of the last command
+    pub fn run_source(&mut self, source: &str) -> u8 {
+        let mut parser = Parser::new(source);
+        // Scripts always have comments, interactive shells only with `interactive_comments`
+        parser.set_comments(!self.interactive || self.shell_options["interactive_comments"]);
+        loop {
+            match parser.next_complete_command(&self.aliases) {
+                Ok(Some(list)) => {
+                    self.execute_list(&list);
+                    // `return` stops a sourced file
+                    if matches!(self.jump, Some(Jump::Exit | Jump::Return)) {
+                        break;
+                    }
+                    // A stray `break` or `continue` does not outlive the command it was used in
+                    self.jump = None;
+                }
+                Ok(None) => break,
+                Err(error) => {
+                    self.print_error(&error.to_string());
+                    self.last_status = 2;
+                    break;
+                }
+            }
+        }
+        self.last_status
+    }
+
+    fn execute_list(&mut self, list: &List) -> u8 {
+        let mut status = 0;
+        for and_or in list {
+            status = match and_or.background {
+                true => self.start_job(and_or),
+                false => self.execute_and_or(and_or),
+            };
+            if self.jump.is_none() {
+                self.run_pending_traps();
+            }
+            // Like other shells, scripts forget the jobs which terminated once they waited for a command
+            // in the foreground, else they can still be waited for
+            if std::mem::take(&mut self.waited_in_foreground) && !self.interactive {
+                self.forget_jobs(self.jobs.terminated());
+            }
+            if self.jump.is_some() {
+                break;
+            }
+        }
+        status
+    }
+
+    // Run the pipelines in a child process in the background, as a new job in its own process group
+    fn start_job(&mut self, and_or: &AndOr) -> u8 {
+        self.write_to_stdout_buffer();
+        match sys::fork() {
+            Ok(ForkResult::Child) => {
+                let _ = sys::set_process_group(0, 0);
+                // Without job control the job does not get to read what is typed in the terminal
+                if !self.interactive {
+                    if let Ok(null) = File::open("/dev/null") {
+                        let _ = sys::dup2(null.as_raw_fd(), 0);
+                    }
+                }
+                self.reset_child();
+                self.exec_external = and_or.rest.is_empty()
+                    && !and_or.first.negated
+                    && matches!(and_or.first.commands.as_slice(), [Command::Simple(_)]);
+                let status = self.execute_and_or(and_or);
+                self.exit_child(status);
+            }
+            Ok(ForkResult::Parent(pid)) => {
+                // Also done by the parent so that the group exists before a signal is sent to it
+                let _ = sys::set_process_group(pid, pid);
+                let id = self.jobs.add(pid, &and_or.source);
+                if self.interactive {
+                    self.print_error(&format!("[{}] {}", id, pid));
+                }
+                self.last_status = 0;
+                0
+            }
+            Err(error) => {
+                self.print_error(&format!("fork: {}", sys::error_message(&error)));
+                self.last_status = 1;
+                1
+            }
+        }
+    }
+
+    // Remove the jobs which terminated, whose statuses are then only kept for `wait pid`
+    // Print the lines announcing the jobs which stopped or terminated
+    pub fn notify_jobs(&mut self) {
+        for line in self.jobs.notifications(self.interactive) {
+            self.print_error(&line);
+        }
+    }
+
+    // Lend the jobs while the shell waits for a line, to be announced from elsewhere with `set -b`
+    pub fn take_jobs(&mut self) -> Jobs {
+        std::mem::take(&mut self.jobs)
+    }
+
+    pub fn restore_jobs(&mut self, jobs: Jobs) {
+        self.jobs = jobs;
+    }
+
+    fn forget_jobs(&mut self, ids: Vec<usize>) {
+        for id in ids {
+            self.jobs.remove(id);
+        }
+    }
+
+    // Wait until every job or, unless `all`, one of them is done, returning the signal which interrupted
+    // the wait to run its trap instead. With job control a stopped job is done too, unless `force`
+    fn wait_for_jobs(&mut self, ids: &[usize], all: bool, force: bool) -> Result<(), libc::c_int> {
+        let stop_ends_wait = self.interactive && !force;
+        let mask = signal::block();
+        let result = loop {
+            self.jobs.reap();
+            let done = |id: &usize| {
+                self.jobs.get(*id).map_or(true, |job| match job.state {
+                    JobState::Running => false,
+                    JobState::Stopped(_) => stop_ends_wait,
+                    _ => true,
+                })
+            };
+            let done = match all {
+                true => ids.iter().all(done),
+                false => ids.iter().any(done),
+            };
+            if done {
+                break Ok(());
+            }
+            if let Some(number) = signal::peek_trapped() {
+                break Err(number);
+            }
+            signal::suspend(&mask);
+        };
+        signal::unblock(&mask);
+        // Scripts report the jobs killed by a signal even when they were waited for
+        if !self.interactive {
+            self.notify_jobs();
+        }
+        result
+    }
+
+    // Run pipelines joined by `&&` and `||`, skipping those whose condition is not met
+    // Every pipeline but the last one is a condition for `errexit`
+    fn execute_and_or(&mut self, and_or: &AndOr) -> u8 {
+        let pipelines: Vec<(Option<AndOrOp>, &Pipeline)> = std::iter::once((None, &and_or.first))
+            .chain(
+                and_or
+                    .rest
+                    .iter()
+                    .map(|(op, pipeline)| (Some(*op), pipeline)),
+            )
+            .collect();
+        let mut status = 0;
+        for (ind, (op, pipeline)) in pipelines.iter().enumerate() {
+            if self.jump.is_some() {
+                break;
+            }
+            if op.is_some_and(|op| (op == AndOrOp::And) != (status == 0)) {
+                continue;
+            }
+            let condition = ind + 1 < pipelines.len();
+            self.condition_depth += condition as usize;
+            status = self.execute_pipeline(pipeline);
+            self.condition_depth -= condition as usize;
+        }
+        status
+    }
+
+    // A negated pipeline is a condition for `errexit`, which otherwise exits the shell when it fails
+    fn execute_pipeline(&mut self, pipeline: &Pipeline) -> u8 {
+        self.condition_depth += pipeline.negated as usize;
+        let mut status = if pipeline.commands.len() == 1 {
+            self.execute_command(&pipeline.commands[0])
+        } else {
+            self.execute_piped_commands(&pipeline.commands)
+        };
+        self.condition_depth -= pipeline.negated as usize;
+        if pipeline.negated {
+            status = (status == 0).into();
+        }
+        self.last_status = status;
+
+        // Failures run the ERR trap and exit with `errexit`, but compound commands like `if` only fail
+        // on their own when a command inside them failed where `errexit` is ignored,
+        // except for `[[ ]]`, `(( ))` and subshells
+        let ignored = matches!(
+            pipeline.commands.as_slice(),
+            [Command::Compound(
+                CompoundCommand::BraceGroup(_)
+                    | CompoundCommand::If { .. }
+                    | CompoundCommand::While { .. }
+                    | CompoundCommand::For { .. },
+                _
+            )]
+        );
+        if status != 0
+            && !pipeline.negated
+            && !ignored
+            && self.condition_depth == 0
+            && self.jump.is_none()
+        {
+            self.run_trap(TrapCondition::Err);
+            if self.option("errexit") && self.jump.is_none() {
+                self.last_status = status;
+                self.jump = Some(Jump::Exit);
+            }
+        }
+        status
+    }
+
+    // Run every command of the pipeline in its own child process, connected by pipes
+    // The status is the one of the last command, or with `pipefail` of the last one which failed
+    fn execute_piped_commands(&mut self, commands: &[Command]) -> u8 {
+        let mut children = Vec::new();
+        let mut previous_output: Option<OwnedFd> = None;
+
+        for (ind, command) in commands.iter().enumerate() {
+            let pipe = if ind + 1 < commands.len() {
+                match sys::pipe() {
+                    Ok(pipe) => Some(pipe),
+                    Err(error) => {
+                        self.print_error(&format!("pipe: {}", sys::error_message(&error)));
+                        break;
+                    }
+                }
+            } else {
+                None
+            };
+
+            match sys::fork() {
+                Ok(ForkResult::Child) => {
+                    if let Some(input) = previous_output.take() {
+                        let _ = sys::dup2(input.as_raw_fd(), 0);
+                    }
+                    if let Some((_, output)) = &pipe {
+                        let _ = sys::dup2(output.as_raw_fd(), 1);
+                    }
+                    drop(pipe);
+                    let jobs = std::mem::take(&mut self.jobs);
+                    self.reset_child();
+                    // Like in other shells, `jobs | cat` still lists the jobs of the shell
+                    let lists_jobs = match command {
+                        Command::Simple(simple) => {
+                            simple.words.first().is_some_and(|word| word == "jobs")
+                        }
+                        _ => false,
+                    };
+                    if lists_jobs {
+                        self.jobs = jobs;
+                    }
+                    self.exec_external = matches!(command, Command::Simple(_));
+                    let status = self.execute_command(command);
+                    self.exit_child(status);
+                }
+                Ok(ForkResult::Parent(pid)) => children.push(pid),
+                Err(error) => {
+                    self.print_error(&format!("fork: {}", sys::error_message(&error)));
+                    break;
+                }
+            }
+            // The write end is closed here so that the next command sees the end of its input
+            previous_output = pipe.map(|(input, _)| input);
+        }
+        drop(previous_output);
+
+        let statuses: Vec<u8> = children
+            .into_iter()
+            .map(|pid| sys::wait_pid(pid).unwrap_or(1))
+            .collect();
+        self.waited_in_foreground = true;
+        match self.option("pipefail") {
+            true => statuses.into_iter().rev().find(|&x| x != 0).unwrap_or(0),
+            false => statuses.last().copied().unwrap_or(1),
+        }
+    }
+
+    // Flush the output and end a forked child process
+    fn exit_child(&mut self, status: u8) -> ! {
+        let status = self.finish(status);
+        self.write_to_stdout_buffer();
+        self.write_to_stderr_buffer();
+        sys::exit(status);
+    }
+
+    fn execute_command(&mut self, command: &Command) -> u8 {
+        match command {
+            Command::Simple(simple) => self.execute_simple(simple),
+            Command::Compound(compound, redirects) => {
+                let saved = match self.apply_redirects(redirects) {
+                    Ok(saved) => saved,
+                    Err(message) => {
+                        self.print_error(&message);
+                        return 1;
+                    }
+                };
+                let status = self.execute_compound(compound);
+                self.restore_redirects(saved);
+                status
+            }
+            Command::FunctionDefinition(function) => {
+                self.functions
+                    .insert(function.name.clone(), Rc::clone(function));
+                0
+            }
+        }
+    }
+
+    fn execute_compound(&mut self, compound: &CompoundCommand) -> u8 {
+        match compound {
+            CompoundCommand::BraceGroup(list) => self.execute_list(list),
+            CompoundCommand::Subshell(list) => {
+                self.write_to_stdout_buffer();
+                match sys::fork() {
+                    Ok(ForkResult::Child) => {
+                        self.reset_child();
+                        let status = self.execute_list(list);
+                        self.exit_child(status);
+                    }
+                    Ok(ForkResult::Parent(pid)) => {
+                        self.waited_in_foreground = true;
+                        sys::wait_pid(pid).unwrap_or(1)
+                    }
+                    Err(error) => {
+                        self.print_error(&format!("fork: {}", sys::error_message(&error)));
+                        1
+                    }
+                }
+            }
+            CompoundCommand::If {
+                branches,
+                else_body,
+            } => {
+                for (condition, body) in branches {
+                    self.condition_depth += 1;
+                    let status = self.execute_list(condition);
+                    self.condition_depth -= 1;
+                    if self.jump.is_some() {
+                        return status;
+                    }
+                    if status == 0 {
+                        return self.execute_list(body);
+                    }
+                }
+                match else_body {
+                    Some(body) => self.execute_list(body),
+                    None => 0,
+                }
+            }
+            CompoundCommand::While {
+                condition,
+                body,
+                until,
+            } => {
+                let mut status = 0;
+                self.loop_depth += 1;
+                loop {
+                    self.condition_depth += 1;
+                    let condition_status = self.execute_list(condition);
+                    self.condition_depth -= 1;
+                    if self.loop_should_stop() || (condition_status == 0) == *until {
+                        break;
+                    }
+                    status = self.execute_list(body);
+                    if self.loop_should_stop() {
+                        break;
+                    }
+                }
+                self.loop_depth -= 1;
+                status
+            }
+            CompoundCommand::For {
+                variable,
+                words,
+                body,
+            } => {
+                let values = match words {
+                    Some(words) => {
+                        let mut values = Vec::new();
+                        for word in words {
+                            match self.expand_word(word) {
+                                Ok(fields) => values.extend(fields),
+                                Err(error) => return self.expansion_failed(error),
+                            }
+                        }
+                        values
+                    }
+                    None => self.positional.clone(),
+                };
+
+                let mut status = 0;
+                self.loop_depth += 1;
+                for value in &values {
+                    if self.option("xtrace") {
+                        let mut words = vec!["for".to_owned(), variable.clone(), "in".to_owned()];
+                        words.extend(values.iter().cloned());
+                        self.print_trace(&quote_words(&words));
+                    }
+                    self.variables.set(variable, value);
+                    status = self.execute_list(body);
+                    if self.loop_should_stop() {
+                        break;
+                    }
+                }
+                self.loop_depth -= 1;
+                status
+            }
+            CompoundCommand::Conditional(expression) => self.evaluate_conditional(expression),
+            // Succeeds when the expression is not zero
+            CompoundCommand::Arithmetic(expression) => match self.expand_arithmetic(expression) {
+                Ok(value) => (value == 0) as u8,
+                Err(error) => self.arithmetic_failed(error),
+            },
+        }
+    }
+
+    // Handle a pending `break` or `continue` at the end of a loop iteration
+    // Returns true if the loop should stop
+    fn loop_should_stop(&mut self) -> bool {
+        match self.jump {
+            Some(Jump::Break(count)) => {
+                self.jump = (count > 1).then_some(Jump::Break(count - 1));
+                true
+            }
+            Some(Jump::Continue(count)) if count > 1 => {
+                self.jump = Some(Jump::Continue(count - 1));
+                true
+            }
+            Some(Jump::Continue(_)) => {
+                self.jump = None;
+                false
+            }
+            Some(Jump::Return) | Some(Jump::Exit) => true,
+            None => false,
+        }
+    }
+
+    fn execute_simple(&mut self, command: &SimpleCommand) -> u8 {
+        let exec = std::mem::take(&mut self.exec_external);
+        self.run_trap(TrapCondition::Debug);
+        let declaration = command
+            .words
+            .first()
+            .is_some_and(|word| DECLARATION_COMMANDS.contains(&word.as_str()));
+        let mut args = Vec::new();
+        // Array assignments given to `declare` or `local` are done once the variable is declared
+        let mut array_assignments = Vec::new();
+        for (i, word) in command.words.iter().enumerate() {
+            let expanded = match split_assignment(word) {
+                Some((target, append, value)) if declaration && i > 0 => {
+                    if value.starts_with('(') {
+                        array_assignments.push((target, append, value));
+                        args.push(target.to_owned());
+                        continue;
+                    }
+                    // Assignments given to declaration commands are not split
+                    self.expand_word_to_string(word).map(|arg| vec![arg])
+                }
+                _ => self.expand_word(word),
+            };
+            match expanded {
+                Ok(fields) => args.extend(fields),
+                Err(error) => return self.expansion_failed(error),
+            }
+        }
+
+        let mut assignments = Vec::new();
+        for assignment in &command.assignments {
+            let (target, append, value) = split_assignment(assignment).unwrap();
+            match self.expand_assigned_value(value) {
+                Ok(value) => assignments.push((target, append, value)),
+                Err(error) => return self.expansion_failed(error),
+            }
+        }
+
+        if self.option("xtrace") {
+            for (ind, (target, append, value)) in assignments.iter().enumerate() {
+                let operator = if *append { "+=" } else { "=" };
+                let value = match value {
+                    AssignedValue::Scalar(value) if value.is_empty() => String::new(),
+                    AssignedValue::Scalar(value) => quote_if_needed(value),
+                    // Arrays are shown as they were written
+                    AssignedValue::Array(_) => {
+                        let (_, _, value) = split_assignment(&command.assignments[ind]).unwrap();
+                        value.to_owned()
+                    }
+                };
+                self.print_trace(&format!("{}{}{}", target, operator, value));
+            }
+            if !args.is_empty() {
+                self.print_trace(&quote_words(&args));
+            }
+        }
+
+        let saved = match self.apply_redirects(&command.redirects) {
+            Ok(saved) => saved,
+            Err(message) => {
+                self.print_error(&message);
+                return 1;
+            }
+        };
+
+        // Only a program can replace the child process, not a function or a builtin
+        self.exec_external = exec
+            && args.first().is_some_and(|name| {
+                !self.functions.contains_key(name) && !BUILTIN_COMMANDS.contains(&name.as_str())
+            });
+        let mut status = if args.is_empty() {
+            // Without a command the assignments change the shell variables
+            let mut status = 0;
+            for (target, append, value) in assignments {
+                if let Err(error) = self.assign(target, append, value) {
+                    status = self.expansion_failed(error);
+                }
+            }
+            status
+        } else if assignments.is_empty() {
+            self.execute(&args)
+        } else {
+            // Assignments before a command are only seen by that command
+            self.variables.push_scope();
+            for (name, append, value) in assignments {
+                let mut value = value.into_scalar();
+                if append {
+                    value = self.variables.get(name).unwrap_or("").to_owned() + &value;
+                }
+                self.variables
+                    .set_local(name, Some(Value::Scalar(value)), true);
+            }
+            let status = self.execute(&args);
+            self.variables.pop_scope();
+            status
+        };
+
+        if status == 0 {
+            for (target, append, value) in array_assignments {
+                let result = self
+                    .expand_assigned_value(value)
+                    .and_then(|value| self.assign(target, append, value));
+                if let Err(error) = result {
+                    status = self.expansion_failed(error);
+                }
+            }
+        }
+
+        self.restore_redirects(saved);
+        status
+    }
+
+    // Apply the redirections to the file descriptors of the shell, returning what is needed to undo them
+    fn apply_redirects(&mut self, redirects: &[Redirect]) -> Result<Vec<SavedFd>, String> {
+        // Anything already written must go to the old destination
+        self.write_to_stdout_buffer();
+        self.write_to_stderr_buffer();
+
+        let mut saved = Vec::new();
+        for redirect in redirects {
+            if let Err(message) = self.apply_redirect(redirect, &mut saved) {
+                self.restore_redirects(saved);
+                return Err(message);
+            }
+        }
+        Ok(saved)
+    }
+
+    fn apply_redirect(
+        &mut self,
+        redirect: &Redirect,
+        saved: &mut Vec<SavedFd>,
+    ) -> Result<(), String> {
+        let target = match redirect.op {
+            RedirectOp::HereDoc => self.expand_here_document(&redirect.target),
+            RedirectOp::QuotedHereDoc => Ok(redirect.target.clone()),
+            _ => self.expand_word_to_string(&redirect.target),
+        }
+        .map_err(|error| error.to_string())?;
+        let fd = match (redirect.fd, redirect.op) {
+            (Some(fd), _) => fd as RawFd,
+            (
+                None,
+                RedirectOp::Input
+                | RedirectOp::ReadWrite
+                | RedirectOp::DupInput
+                | RedirectOp::HereDoc
+                | RedirectOp::QuotedHereDoc,
+            ) => 0,
+            (None, _) => 1,
+        };
+
+        // Keep a copy of the file descriptor the first time it is replaced
+        let mut save = |fd: RawFd| {
+            if !saved.iter().any(|x| x.fd == fd) {
+                saved.push(SavedFd {
+                    fd,
+                    original: sys::dup_high(fd).ok(),
+                });
+            }
+        };
+
+        let path = encoding::to_os_string(&target);
+        let file = match redirect.op {
+            RedirectOp::Input => File::open(&path),
+            RedirectOp::Output | RedirectOp::OutputAll => File::create(&path), // This is also a wrapper over `OpenOptions`
+            RedirectOp::Append | RedirectOp::AppendAll => {
+                OpenOptions::new().append(true).create(true).open(&path)
+            }
+            RedirectOp::ReadWrite => OpenOptions::new()
+                .read(true)
+                .write(true)
+                .create(true)
+                .truncate(false)
+                .open(&path),
+            RedirectOp::HereDoc | RedirectOp::QuotedHereDoc => Ok(sys::text_file(&target)
+                .map_err(|error| {
+                    format!(
+                        "cannot create temp file for here-document: {}",
+                        sys::error_message(&error)
+                    )
+                })?),
+            RedirectOp::DupInput | RedirectOp::DupOutput => {
+                // `>&-` closes the file descriptor
+                if target == "-" {
+                    save(fd);
+                    sys::close(fd);
+                    return Ok(());
+                }
+                let source = match target.parse::<RawFd>() {
+                    Ok(source) => source,
+                    Err(_) => return Err(format!("{}: ambiguous redirect", target)),
+                };
+                save(fd);
+                return sys::dup2(source, fd)
+                    .map_err(|error| format!("{}: {}", source, sys::error_message(&error)));
+            }
+        }
+        .map_err(|error| format!("{}: {}", target, sys::error_message(&error)))?;
+
+        let fds = match redirect.op {
+            RedirectOp::OutputAll | RedirectOp::AppendAll => vec![1, 2],
+            _ => vec![fd],
+        };
+        for fd in fds {
+            save(fd);
+            sys::dup2(file.as_raw_fd(), fd)
+                .map_err(|error| format!("{}: {}", fd, sys::error_message(&error)))?;
+        }
+        Ok(())
+    }
+
+    // Put back the file descriptors replaced by `apply_redirects`
+    fn restore_redirects(&mut self, saved: Vec<SavedFd>) {
+        self.write_to_stdout_buffer();
+        self.write_to_stderr_buffer();
+        for saved_fd in saved.into_iter().rev() {
+            match saved_fd.original {
+                Some(original) => {
+                    let _ = sys::dup2(original.as_raw_fd(), saved_fd.fd);
+                }
+                None => sys::close(saved_fd.fd),
+            }
+        }
+    }
+
+    // Run a shell function with the arguments as its positional parameters
+    fn call_function(&mut self, function: &FunctionDefinition, args: &[String]) -> u8 {
+        let saved_positional = std::mem::replace(&mut self.positional, args[1..].to_vec());
+        self.variables.push_scope();
+        self.function_depth += 1;
+
+        let mut status = self.execute_command(&function.body);
+        if self.jump == Some(Jump::Return) {
+            self.jump = None;
+            status = self.last_status;
+        }
+        self.run_trap(TrapCondition::Return);
+
+        self.function_depth -= 1;
+        self.variables.pop_scope();
+        self.positional = saved_positional;
+        status
+    }
+
+    // Run a builtin command, returning `None` if there is no builtin with this name
+    fn execute_builtin(&mut self, args: &[String]) -> Option<u8> {
+        let status = match args[0].as_str() {
+            "exit" if !self.may_exit() => 1,
+            "exit" => {
+                self.jump = Some(Jump::Exit);
+                self.parse_exit_code(args)
+            }
+            ":" | "true" => 0,
+            "false" => 1,
+            "echo" => self.builtin_echo(args),
+            "pwd" => self.builtin_pwd(),
+            "cd" => self.builtin_cd(args),
+            "type" => self.builtin_type(args),
+            "export" => self.builtin_export(args),
+            "unset" => self.builtin_unset(args),
+            "local" => self.builtin_local(args),
+            "return" => self.builtin_return(args),
+            "break" | "continue" => self.builtin_break_continue(args),
+            "command" => self.builtin_command(args),
+            "builtin" => self.builtin_builtin(args),
+            "alias" => self.builtin_alias(args),
+            "unalias" => self.builtin_unalias(args),
+            "let" => self.builtin_let(args),
+            "declare" | "typeset" => self.builtin_declare(args),
+            "test" | "[" => self.builtin_test(args),
+            "shopt" => self.builtin_shopt(args),
+            "set" => self.builtin_set(args),
+            "trap" => self.builtin_trap(args),
+            "jobs" => self.builtin_jobs(args),
+            "wait" => self.builtin_wait(args),
+            "kill" => self.builtin_kill(args),
+            "disown" => self.builtin_disown(args),
+            "source" | "." => self.builtin_source(args),
+            _ => return None,
+        };
+        self.write_to_stdout_buffer(); // Write the output of the command to the output buffer
+        self.write_to_stderr_buffer(); // Write the error of the command to the error buffer
+        Some(status)
+    }
+
+    fn execute_builtin_or_external(&mut self, args: &[String]) -> u8 {
+        match self.execute_builtin(args) {
+            Some(status) => status,
+            None => self.execute_external(args),
+        }
+    }
+
+    // Run the command in a child process if found in PATH
+    fn execute_external(&mut self, args: &[String]) -> u8 {
+        let exec = std::mem::take(&mut self.exec_external);
+        let command_path = match self.get_absolute_command_path(&args[0]) {
+            Some(command_path) => command_path,
+            None => {
+                self.stdout_buffer += &format!("{}: command not found\n", args[0]);
+                self.write_to_stdout_buffer();
+                return 127; // Command not found error code
+            }
+        };
+
+        let environment = self
+            .variables
+            .exported()
+            .into_iter()
+            .map(|(name, value)| (encoding::to_os_string(name), encoding::to_os_string(value)));
+        let mut command = ExternalCommand::new(encoding::to_os_string(&command_path));
+        command
+            .arg0(encoding::to_os_string(&args[0]))
+            .args(args[1..].iter().map(|arg| encoding::to_os_string(arg)))
+            .env_clear()
+            .envs(environment);
+        if exec {
+            self.write_to_stdout_buffer();
+            self.write_to_stderr_buffer();
+            // Only returns if the program could not be run
+            let error = command.exec();
+            self.print_error(&format!("{}: {}", args[0], sys::error_message(&error)));
+            return 126;
+        }
+        let status = match command.spawn().and_then(|mut child| child.wait()) {
+            Ok(status) => {
+                self.waited_in_foreground = true;
+                status
+            }
+            Err(error) => {
+                self.print_error(&format!("{}: {}", args[0], sys::error_message(&error)));
+                return 126;
+            }
+        };
+
+        // A command killed by a signal has no exit code
+        match status.code() {
+            Some(code) => code as u8,
+            None => 128 + status.signal().unwrap_or(0) as u8,
+        }
+    }
+
+    // Execute the command with args and return appropriate status code
+    // Shell functions take precedence over builtins and commands in PATH
+    pub fn execute(&mut self, args: &[String]) -> u8 {
+        // Exiting despite stopped jobs needs to be asked for again right away
+        if args[0] != "exit" {
+            self.exit_warned = false;
+        }
+        if let Some(function) = self.functions.get(&args[0]).cloned() {
+            return self.call_function(&function, args);
         }
+        self.execute_builtin_or_external(args)
     }
 }
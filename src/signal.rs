@@ -0,0 +1,211 @@
+// Signals known by name, for `trap` and `kill`, and the handler which lets the shell run a trap
+// The handler only records that the signal arrived, its trap runs later between two commands
+use std::{
+    ffi::CStr,
+    io, mem,
+    os::fd::{IntoRawFd, OwnedFd},
+    ptr,
+    sync::atomic::{AtomicBool, AtomicI32, Ordering},
+};
+
+use libc::c_int;
+
+// Names of the signals without the `SIG` prefix
+pub const SIGNALS: [(&str, c_int); 29] = [
+    ("HUP", libc::SIGHUP),
+    ("INT", libc::SIGINT),
+    ("QUIT", libc::SIGQUIT),
+    ("ILL", libc::SIGILL),
+    ("TRAP", libc::SIGTRAP),
+    ("ABRT", libc::SIGABRT),
+    ("BUS", libc::SIGBUS),
+    ("FPE", libc::SIGFPE),
+    ("KILL", libc::SIGKILL),
+    ("USR1", libc::SIGUSR1),
+    ("SEGV", libc::SIGSEGV),
+    ("USR2", libc::SIGUSR2),
+    ("PIPE", libc::SIGPIPE),
+    ("ALRM", libc::SIGALRM),
+    ("TERM", libc::SIGTERM),
+    ("CHLD", libc::SIGCHLD),
+    ("CONT", libc::SIGCONT),
+    ("STOP", libc::SIGSTOP),
+    ("TSTP", libc::SIGTSTP),
+    ("TTIN", libc::SIGTTIN),
+    ("TTOU", libc::SIGTTOU),
+    ("URG", libc::SIGURG),
+    ("XCPU", libc::SIGXCPU),
+    ("XFSZ", libc::SIGXFSZ),
+    ("VTALRM", libc::SIGVTALRM),
+    ("PROF", libc::SIGPROF),
+    ("WINCH", libc::SIGWINCH),
+    ("IO", libc::SIGIO),
+    ("SYS", libc::SIGSYS),
+];
+
+// Signals which arrived and whose trap has not run yet, indexed by signal number
+static PENDING: [AtomicBool; 65] = [const { AtomicBool::new(false) }; 65];
+
+// A pipe written to when SIGCHLD arrives, to wake up what announces the jobs while a line is read
+static CHILD_PIPE: AtomicI32 = AtomicI32::new(-1);
+
+// The signal for a number or a name like `INT`, `SIGINT` or `sigint`
+pub fn number(spec: &str) -> Option<c_int> {
+    if let Ok(number) = spec.parse::<c_int>() {
+        return SIGNALS
+            .iter()
+            .any(|(_, signal)| *signal == number)
+            .then_some(number);
+    }
+    let spec = spec.to_ascii_uppercase();
+    let name = spec.strip_prefix("SIG").unwrap_or(&spec);
+    SIGNALS
+        .iter()
+        .find(|(signal_name, _)| *signal_name == name)
+        .map(|(_, signal)| *signal)
+}
+
+// The name of the signal without the `SIG` prefix
+pub fn name(signal: c_int) -> Option<&'static str> {
+    SIGNALS
+        .iter()
+        .find(|(_, number)| *number == signal)
+        .map(|(name, _)| *name)
+}
+
+// The table printed by `kill -l` and `trap -l`, five signals to a line like ` 2) SIGINT`
+pub fn table() -> String {
+    let mut signals = SIGNALS.to_vec();
+    signals.sort_by_key(|(_, number)| *number);
+    signals
+        .chunks(5)
+        .map(|row| {
+            let row: Vec<String> = row
+                .iter()
+                .map(|(name, number)| format!("{:2}) SIG{}", number, name))
+                .collect();
+            row.join("\t") + "\n"
+        })
+        .collect()
+}
+
+extern "C" fn record_signal(signal: c_int) {
+    if let Some(pending) = PENDING.get(signal as usize) {
+        pending.store(true, Ordering::SeqCst);
+    }
+    let fd = CHILD_PIPE.load(Ordering::SeqCst);
+    if signal == libc::SIGCHLD && fd >= 0 {
+        // Safety: `write` is async-signal-safe and the pipe does not block when it is full
+        unsafe { libc::write(fd, b"c".as_ptr().cast(), 1) };
+    }
+}
+
+fn set_handler(signal: c_int, handler: libc::sighandler_t) -> io::Result<()> {
+    // Safety: the handler only stores to an atomic and writes to a pipe, which are safe to do in a signal handler
+    let result = unsafe {
+        let mut action: libc::sigaction = mem::zeroed();
+        action.sa_sigaction = handler;
+        action.sa_flags = libc::SA_RESTART;
+        libc::sigemptyset(&mut action.sa_mask);
+        libc::sigaction(signal, &action, ptr::null_mut())
+    };
+    match result {
+        -1 => Err(io::Error::last_os_error()),
+        _ => Ok(()),
+    }
+}
+
+// Record the signal when it arrives, for its trap to run at the next safe point
+pub fn catch(signal: c_int) -> io::Result<()> {
+    set_handler(
+        signal,
+        record_signal as extern "C" fn(c_int) as libc::sighandler_t,
+    )
+}
+
+// Write a byte to the pipe whenever SIGCHLD arrives, which stays open for the rest of the process
+pub fn wake_on_child(pipe: OwnedFd) -> io::Result<()> {
+    let fd = pipe.into_raw_fd();
+    if unsafe { libc::fcntl(fd, libc::F_SETFL, libc::O_NONBLOCK) } == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    CHILD_PIPE.store(fd, Ordering::SeqCst);
+    Ok(())
+}
+
+// Ignore the signal, which child processes keep ignoring as well
+// SIGCHLD is always caught since the shell needs it to know when its jobs finish
+pub fn ignore(signal: c_int) -> io::Result<()> {
+    match signal {
+        libc::SIGCHLD => catch(signal),
+        _ => set_handler(signal, libc::SIG_IGN),
+    }
+}
+
+// Go back to the default action of the signal, like terminating the process
+pub fn reset(signal: c_int) -> io::Result<()> {
+    match signal {
+        libc::SIGCHLD => catch(signal),
+        _ => set_handler(signal, libc::SIG_DFL),
+    }
+}
+
+// Whether the signal is ignored, like when the program which started the shell ignored it
+pub fn is_ignored(signal: c_int) -> bool {
+    // Safety: passing a null action only reads the current one
+    unsafe {
+        let mut action: libc::sigaction = mem::zeroed();
+        libc::sigaction(signal, ptr::null(), &mut action) == 0
+            && action.sa_sigaction == libc::SIG_IGN
+    }
+}
+
+// The signals which arrived since the last call, in order of their numbers
+pub fn take_pending() -> Vec<c_int> {
+    (0..PENDING.len())
+        .filter(|&signal| PENDING[signal].swap(false, Ordering::SeqCst))
+        .map(|signal| signal as c_int)
+        .collect()
+}
+
+// The first signal which arrived and is still waiting for its trap, leaving it pending
+// SIGCHLD is left out, it only tells that a job may have finished
+pub fn peek_trapped() -> Option<c_int> {
+    (0..PENDING.len())
+        .find(|&signal| signal as c_int != libc::SIGCHLD && PENDING[signal].load(Ordering::SeqCst))
+        .map(|signal| signal as c_int)
+}
+
+// Hold back every signal until `suspend` or `unblock`, returning the mask to restore afterwards
+// A signal arriving between checking for it and going to sleep then wakes up `suspend` instead of being missed
+pub fn block() -> libc::sigset_t {
+    // Safety: the sets are initialized by `sigfillset` and `sigprocmask` before being used
+    unsafe {
+        let mut all: libc::sigset_t = mem::zeroed();
+        let mut previous: libc::sigset_t = mem::zeroed();
+        libc::sigfillset(&mut all);
+        libc::sigprocmask(libc::SIG_BLOCK, &all, &mut previous);
+        previous
+    }
+}
+
+// Sleep with the mask returned by `block` until a signal arrives and its handler ran
+pub fn suspend(mask: &libc::sigset_t) {
+    unsafe { libc::sigsuspend(mask) };
+}
+
+pub fn unblock(mask: &libc::sigset_t) {
+    unsafe { libc::sigprocmask(libc::SIG_SETMASK, mask, ptr::null_mut()) };
+}
+
+// How the signal is described when it ends a job, like `Terminated` for SIGTERM
+pub fn description(signal: c_int) -> String {
+    // Safety: `strsignal` returns a string which stays valid until the next call
+    let description = unsafe { libc::strsignal(signal) };
+    if description.is_null() {
+        return format!("Signal {}", signal);
+    }
+    unsafe { CStr::from_ptr(description) }
+        .to_string_lossy()
+        .into_owned()
+}
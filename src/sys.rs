@@ -0,0 +1,144 @@
+// Thin wrappers over the libc calls needed to run pipelines, subshells and redirections
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, Seek, Write},
+    mem::ManuallyDrop,
+    os::fd::{FromRawFd, OwnedFd, RawFd},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use crate::encoding;
+
+pub enum ForkResult {
+    Parent(libc::pid_t),
+    Child,
+}
+
+// Convert the -1 returned by libc on failure into an `io::Error`
+fn check(result: libc::c_int) -> io::Result<libc::c_int> {
+    if result == -1 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(result)
+    }
+}
+
+// Error message without the " (os error N)" suffix added by `io::Error`
+pub fn error_message(error: &io::Error) -> String {
+    let message = error.to_string();
+    match message.find(" (os error") {
+        Some(ind) => message[..ind].to_owned(),
+        None => message,
+    }
+}
+
+pub fn fork() -> io::Result<ForkResult> {
+    // Safety: the only other thread of the shell touches nothing but the jobs lent to it while a line is read, so the child gets a consistent copy of the process
+    match check(unsafe { libc::fork() })? {
+        0 => Ok(ForkResult::Child),
+        pid => Ok(ForkResult::Parent(pid)),
+    }
+}
+
+// Create a pipe returning the read and write ends, both closed when executing another program
+pub fn pipe() -> io::Result<(OwnedFd, OwnedFd)> {
+    let mut fds = [0; 2];
+    check(unsafe { libc::pipe(fds.as_mut_ptr()) })?;
+    for fd in fds {
+        check(unsafe { libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC) })?;
+    }
+    // Safety: both file descriptors were just created by `pipe`
+    Ok(unsafe { (OwnedFd::from_raw_fd(fds[0]), OwnedFd::from_raw_fd(fds[1])) })
+}
+
+// Make `new_fd` refer to the same file as `old_fd`
+pub fn dup2(old_fd: RawFd, new_fd: RawFd) -> io::Result<()> {
+    check(unsafe { libc::dup2(old_fd, new_fd) })?;
+    Ok(())
+}
+
+// Duplicate the file descriptor to a high number, out of the way of user redirections
+pub fn dup_high(fd: RawFd) -> io::Result<OwnedFd> {
+    let new_fd = check(unsafe { libc::fcntl(fd, libc::F_DUPFD_CLOEXEC, 10) })?;
+    Ok(unsafe { OwnedFd::from_raw_fd(new_fd) })
+}
+
+// An unnamed file holding the text, ready to be read from the start, used for here-documents
+// The file is removed right after it is created so nothing is left behind
+pub fn text_file(text: &str) -> io::Result<File> {
+    static COUNT: AtomicUsize = AtomicUsize::new(0);
+    let name = format!(
+        "sh-here-document-{}-{}",
+        std::process::id(),
+        COUNT.fetch_add(1, Ordering::Relaxed)
+    );
+    let path = std::env::temp_dir().join(name);
+    let mut file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create_new(true)
+        .open(&path)?;
+    fs::remove_file(&path)?;
+    file.write_all(&encoding::encode(text))?;
+    file.rewind()?;
+    Ok(file)
+}
+
+// Write all the bytes to a file descriptor which stays open afterwards, like the one of `BASH_XTRACEFD`
+pub fn write_fd(fd: RawFd, bytes: &[u8]) -> io::Result<()> {
+    if fd < 0 {
+        return Err(io::Error::from_raw_os_error(libc::EBADF));
+    }
+    // Safety: the file is never dropped so the file descriptor is not closed
+    let mut file = ManuallyDrop::new(unsafe { File::from_raw_fd(fd) });
+    file.write_all(bytes)
+}
+
+pub fn close(fd: RawFd) {
+    unsafe { libc::close(fd) };
+}
+
+// Wait for the child process to finish and return its exit status
+// A child killed by a signal gets 128 + the signal number like in other shells
+pub fn wait_pid(pid: libc::pid_t) -> io::Result<u8> {
+    let mut status = 0;
+    loop {
+        match check(unsafe { libc::waitpid(pid, &mut status, 0) }) {
+            Ok(_) => break,
+            Err(error) if error.kind() == io::ErrorKind::Interrupted => continue,
+            Err(error) => return Err(error),
+        }
+    }
+    if libc::WIFSIGNALED(status) {
+        Ok(128 + libc::WTERMSIG(status) as u8)
+    } else {
+        Ok(libc::WEXITSTATUS(status) as u8)
+    }
+}
+
+// Send the signal to the process, or to the process group `-pid` when negative
+pub fn kill(pid: libc::pid_t, signal: libc::c_int) -> io::Result<()> {
+    check(unsafe { libc::kill(pid, signal) })?;
+    Ok(())
+}
+
+// Put the process in the process group, a new one led by it when `group` is its own pid
+pub fn set_process_group(pid: libc::pid_t, group: libc::pid_t) -> io::Result<()> {
+    check(unsafe { libc::setpgid(pid, group) })?;
+    Ok(())
+}
+
+// The raw status of the child process if it finished, stopped or continued, without waiting for it
+pub fn poll_pid(pid: libc::pid_t) -> io::Result<Option<libc::c_int>> {
+    let mut status = 0;
+    let flags = libc::WNOHANG | libc::WUNTRACED | libc::WCONTINUED;
+    match check(unsafe { libc::waitpid(pid, &mut status, flags) })? {
+        0 => Ok(None),
+        _ => Ok(Some(status)),
+    }
+}
+
+// Exit the process right away without running destructors, used by forked children
+pub fn exit(status: u8) -> ! {
+    unsafe { libc::_exit(status as libc::c_int) }
+}
@@ -0,0 +1,251 @@
+use std::collections::{BTreeMap, HashMap};
+
+use crate::encoding;
+
+#[derive(Debug, Clone)]
+pub enum Value {
+    Scalar(String),
+    // Indexed arrays can have gaps, so they map each index which is set to its element
+    Indexed(BTreeMap<i64, String>),
+    Associative(BTreeMap<String, String>),
+}
+
+impl Value {
+    // Element for the key, where a scalar acts as an array holding only the element 0
+    // The keys of indexed arrays are their indices written in decimal
+    pub fn element(&self, key: &str) -> Option<&str> {
+        match self {
+            Value::Scalar(value) => (key == "0").then_some(value.as_str()),
+            Value::Indexed(array) => array.get(&key.parse().ok()?).map(|x| x.as_str()),
+            Value::Associative(array) => array.get(key).map(|x| x.as_str()),
+        }
+    }
+
+    // All the elements along with their keys, in order
+    pub fn entries(&self) -> Vec<(String, String)> {
+        match self {
+            Value::Scalar(value) => vec![("0".to_owned(), value.clone())],
+            Value::Indexed(array) => array
+                .iter()
+                .map(|(index, value)| (index.to_string(), value.clone()))
+                .collect(),
+            Value::Associative(array) => array
+                .iter()
+                .map(|(key, value)| (key.clone(), value.clone()))
+                .collect(),
+        }
+    }
+
+    pub fn values(&self) -> Vec<String> {
+        self.entries().into_iter().map(|(_, value)| value).collect()
+    }
+
+    pub fn keys(&self) -> Vec<String> {
+        self.entries().into_iter().map(|(key, _)| key).collect()
+    }
+
+    // The index after the last element, where `+=` adds new elements
+    pub fn next_index(&self) -> i64 {
+        match self {
+            Value::Scalar(_) => 1,
+            Value::Indexed(array) => array.keys().last().map_or(0, |index| index + 1),
+            Value::Associative(_) => 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Variable {
+    // `None` for a variable which is declared (like `local x`) but has no value yet
+    pub value: Option<Value>,
+    pub exported: bool,
+}
+
+// Shell variables stored as a stack of scopes
+// The first scope holds the global variables, every function call pushes a new one for its `local`s
+// Lookups walk the stack from the innermost scope, which gives bash's dynamic scoping
+pub struct Variables {
+    scopes: Vec<HashMap<String, Variable>>,
+    // `set -a`, which exports every variable assigned to
+    pub export_all: bool,
+}
+
+impl Variables {
+    // Start with the environment of the process, all of which is exported
+    pub fn from_env() -> Self {
+        let globals = std::env::vars_os()
+            .map(|(name, value)| {
+                let variable = Variable {
+                    value: Some(Value::Scalar(encoding::from_os_str(&value))),
+                    exported: true,
+                };
+                (encoding::from_os_str(&name), variable)
+            })
+            .collect();
+        Self {
+            scopes: vec![globals],
+            export_all: false,
+        }
+    }
+
+    pub fn push_scope(&mut self) {
+        self.scopes.push(HashMap::new());
+    }
+
+    pub fn pop_scope(&mut self) {
+        if self.scopes.len() > 1 {
+            self.scopes.pop();
+        }
+    }
+
+    pub fn get_variable(&self, name: &str) -> Option<&Variable> {
+        self.scopes.iter().rev().find_map(|scope| scope.get(name))
+    }
+
+    pub fn get_value(&self, name: &str) -> Option<&Value> {
+        self.get_variable(name)?.value.as_ref()
+    }
+
+    // Value of the variable, `None` if it is unset
+    // For an array this is its element 0
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.get_value(name)?.element("0")
+    }
+
+    // The innermost scope which has the variable, or the global one
+    fn scope_of(&mut self, name: &str) -> &mut HashMap<String, Variable> {
+        let ind = self
+            .scopes
+            .iter()
+            .rposition(|scope| scope.contains_key(name))
+            .unwrap_or(0);
+        &mut self.scopes[ind]
+    }
+
+    // The variable about to be assigned to, created if needed and exported with `export_all`
+    fn variable_mut(&mut self, name: &str) -> &mut Variable {
+        let export_all = self.export_all;
+        let variable = self.scope_of(name).entry(name.to_owned()).or_default();
+        variable.exported |= export_all;
+        variable
+    }
+
+    // Assign to the variable in the innermost scope which has it, or create a global
+    // Assigning to an array without a subscript sets its element 0
+    pub fn set(&mut self, name: &str, value: &str) {
+        let variable = self.variable_mut(name);
+        match &mut variable.value {
+            Some(Value::Indexed(array)) => {
+                array.insert(0, value.to_owned());
+            }
+            Some(Value::Associative(array)) => {
+                array.insert("0".to_owned(), value.to_owned());
+            }
+            _ => variable.value = Some(Value::Scalar(value.to_owned())),
+        }
+    }
+
+    // Replace the whole value of the variable, like `set` does for scalars
+    pub fn set_value(&mut self, name: &str, value: Value) {
+        self.variable_mut(name).value = Some(value);
+    }
+
+    // Set one element of an array, turning the variable into an indexed array if it is not one
+    pub fn set_element(&mut self, name: &str, key: &str, value: &str) {
+        let variable = self.variable_mut(name);
+        match &mut variable.value {
+            Some(Value::Associative(array)) => {
+                array.insert(key.to_owned(), value.to_owned());
+            }
+            Some(Value::Indexed(array)) => {
+                array.insert(key.parse().unwrap_or(0), value.to_owned());
+            }
+            current => {
+                let mut array = BTreeMap::new();
+                if let Some(Value::Scalar(scalar)) = current.take() {
+                    array.insert(0, scalar);
+                }
+                array.insert(key.parse().unwrap_or(0), value.to_owned());
+                *current = Some(Value::Indexed(array));
+            }
+        }
+    }
+
+    pub fn unset_element(&mut self, name: &str, key: &str) {
+        let variable = self.variable_mut(name);
+        match &mut variable.value {
+            Some(Value::Associative(array)) => {
+                array.remove(key);
+            }
+            Some(Value::Indexed(array)) => {
+                if let Ok(index) = key.parse() {
+                    array.remove(&index);
+                }
+            }
+            Some(Value::Scalar(_)) if key == "0" => variable.value = None,
+            _ => {}
+        }
+    }
+
+    // Create the variable in the innermost scope, hiding any outer variable with the same name
+    pub fn set_local(&mut self, name: &str, value: Option<Value>, exported: bool) {
+        let export_all = self.export_all && value.is_some();
+        let scope = self.scopes.last_mut().unwrap();
+        let variable = scope.entry(name.to_owned()).or_default();
+        variable.value = value;
+        variable.exported |= exported || export_all;
+    }
+
+    // Whether the innermost scope has the variable, like after `local`
+    pub fn is_local(&self, name: &str) -> bool {
+        self.scopes.len() > 1 && self.scopes.last().unwrap().contains_key(name)
+    }
+
+    // Mark the variable for export to child processes, creating it if needed
+    pub fn export(&mut self, name: &str) {
+        self.variable_mut(name).exported = true;
+    }
+
+    // Remove the variable from the innermost scope which has it
+    pub fn unset(&mut self, name: &str) {
+        if let Some(scope) = self
+            .scopes
+            .iter_mut()
+            .rev()
+            .find(|scope| scope.contains_key(name))
+        {
+            scope.remove(name);
+        }
+    }
+
+    // Sorted names of all the variables which have a value
+    pub fn names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self
+            .scopes
+            .iter()
+            .flat_map(|scope| scope.keys())
+            .map(|name| name.as_str())
+            .filter(|name| self.get_value(name).is_some())
+            .collect();
+        names.sort();
+        names.dedup();
+        names
+    }
+
+    // The exported variables with a value, as seen by a child process
+    // Arrays can not be put in the environment so they are left out
+    pub fn exported(&self) -> HashMap<&str, &str> {
+        let mut environment = HashMap::new();
+        for scope in &self.scopes {
+            for (name, variable) in scope {
+                match (&variable.value, variable.exported) {
+                    (Some(Value::Scalar(value)), true) => {
+                        environment.insert(name.as_str(), value.as_str())
+                    }
+                    _ => environment.remove(name.as_str()),
+                };
+            }
+        }
+        environment
+    }
+}
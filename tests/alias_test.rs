@@ -0,0 +1,68 @@
+mod common;
+use common::run_shell;
+
+#[test]
+fn test_alias_expansion() {
+    let input_sequence: Vec<&str> = vec![
+        "mkdir -p /tmp/alias_test && touch /tmp/alias_test/apple /tmp/alias_test/banana",
+        "alias ll='ls -1'",
+        "ll /tmp/alias_test",
+        // An alias is not expanded again inside its own value
+        "alias ls='ls -1'",
+        "ls /tmp/alias_test",
+        // A trailing space makes the next word an alias candidate too
+        "alias say='echo said: ' word=expanded",
+        "say word",
+        "alias hi=greeting greeting=hi",
+        "hi",
+        // Quoted words are never aliases
+        "'ll'",
+        "rm -rf /tmp/alias_test",
+    ];
+
+    let expected_output_sequence: Vec<&str> = vec![
+        "apple",
+        "banana",
+        "apple",
+        "banana",
+        "said: expanded",
+        "hi: command not found",
+        "ll: command not found",
+    ];
+
+    let expected_error_sequence: Vec<&str> = vec![];
+
+    let (stdout, stderr) = run_shell(&input_sequence);
+    assert_eq!(stdout, expected_output_sequence);
+    assert_eq!(stderr, expected_error_sequence);
+}
+
+#[test]
+fn test_alias_listing() {
+    let input_sequence: Vec<&str> = vec![
+        "alias ll='ls -l' q=\"it's\"",
+        "alias",
+        "alias ll",
+        "type ll",
+        "unalias ll",
+        "type ll",
+        "unalias ll",
+        "unalias -a",
+        "alias",
+        "alias q",
+    ];
+
+    let expected_output_sequence: Vec<&str> = vec![
+        "alias ll='ls -l'",
+        r"alias q='it'\''s'",
+        "alias ll='ls -l'",
+        "ll is aliased to `ls -l'",
+        "ll: not found",
+    ];
+
+    let expected_error_sequence: Vec<&str> = vec!["unalias: ll: not found", "alias: q: not found"];
+
+    let (stdout, stderr) = run_shell(&input_sequence);
+    assert_eq!(stdout, expected_output_sequence);
+    assert_eq!(stderr, expected_error_sequence);
+}
@@ -0,0 +1,46 @@
+mod common;
+use common::run_shell;
+
+#[test]
+fn test_ansi_c_quoting() {
+    let input_sequence: Vec<&str> = vec![
+        r"echo $'a\tb' $'\x1b[0m' $'é' $'\101\102'",
+        r#"echo $'it\'s' $'\"\\' $'\q' x$'y'z"#,
+        r"echo $'line\nnext'",
+        // Control characters, and a NUL which ends the string
+        r"[[ $'\cA\ca' == $'\x01\001' ]] && echo control",
+        r"echo $'kept\0dropped'",
+        // Inside double quotes `$'` is not special
+        r#"echo "$'x'""#,
+    ];
+
+    let expected_output_sequence: Vec<&str> = vec![
+        "a\tb \x1b[0m é AB",
+        r#"it's "\ \q xyz"#,
+        "line",
+        "next",
+        "control",
+        "kept",
+        "$'x'",
+    ];
+
+    let (stdout, stderr) = run_shell(&input_sequence);
+    assert_eq!(stdout, expected_output_sequence);
+    assert!(stderr.is_empty());
+}
+
+#[test]
+fn test_locale_quoting() {
+    let input_sequence: Vec<&str> = vec![
+        "x=world",
+        r#"echo $"hello $x" $"a  b""#,
+        r#"a=($"one two" three)"#,
+        "echo ${#a[@]}",
+    ];
+
+    let expected_output_sequence: Vec<&str> = vec!["hello world a  b", "2"];
+
+    let (stdout, stderr) = run_shell(&input_sequence);
+    assert_eq!(stdout, expected_output_sequence);
+    assert!(stderr.is_empty());
+}
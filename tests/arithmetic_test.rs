@@ -0,0 +1,79 @@
+mod common;
+use common::{run_shell, run_with_args};
+
+#[test]
+fn test_arithmetic_expansion() {
+    let input_sequence: Vec<&str> = vec![
+        "echo $((1 + 2 * 3)) $((2 ** 10)) $((7 / 2)) $((7 % 3)) $((-7 / 2))",
+        "echo $((1 << 4)) $((5 & 3)) $((5 | 3)) $((5 ^ 3)) $((~0)) $((!5))",
+        "echo $((16#ff)) $((0x10)) $((010)) $((2#101)) $((1 ? 2 : 3)) $((1, 2))",
+        // Variables can be used with or without `$`, and hold expressions themselves
+        "x=5; echo $((x + 1)) $(($x * 2)) $((x++)) $x $((++x)) $((x += 10)) $x",
+        "e='1 + 2'; echo $((e * 3))",
+        // The right side of `&&` and `||` is only evaluated when needed
+        "y=0; echo $((0 && (y = 1))) $y $((1 || (y = 2))) $y",
+        "s=hello; echo ${s:1+1} ${s:(-3):2}",
+    ];
+
+    let expected_output_sequence: Vec<&str> = vec![
+        "7 1024 3 1 -3",
+        "16 1 7 6 -1 0",
+        "255 16 8 5 2 2",
+        "6 10 5 6 7 17 17",
+        "9",
+        "0 0 1 0",
+        "llo ll",
+    ];
+
+    let (stdout, stderr) = run_shell(&input_sequence);
+    assert_eq!(stdout, expected_output_sequence);
+    assert!(stderr.is_empty());
+}
+
+#[test]
+fn test_arithmetic_errors() {
+    // An interactive shell reports the error and goes on with the next command
+    let (status, stdout, stderr) = run_with_args(
+        &["--norc", "-i"],
+        "echo $((1 / 0)); echo $?\necho $((1 +))\n",
+    );
+    assert_eq!(status, 1);
+    assert_eq!(stdout, vec!["1"]);
+    assert_eq!(
+        stderr,
+        vec![
+            r#"1 / 0: division by 0 (error token is "0")"#,
+            r#"1 +: syntax error: operand expected (error token is "")"#,
+        ]
+    );
+
+    // Any other shell exits on an invalid expansion, while `(( ))` and `[[ ]]` only fail
+    let commands = "((1/0)); echo $?; [[ 1/0 -eq 0 ]]; echo $?; x=$((1/0)); echo after";
+    let (status, stdout, stderr) = run_with_args(&["-c", commands], "");
+    assert_eq!(status, 1);
+    assert_eq!(stdout, vec!["1", "1"]);
+    assert_eq!(
+        stderr,
+        vec![r#"1/0: division by 0 (error token is "0")"#; 3]
+    );
+}
+
+#[test]
+fn test_arithmetic_command_and_let() {
+    let input_sequence: Vec<&str> = vec![
+        "(( 0 )); echo $?",
+        "(( 5 > 3 )); echo $?",
+        "i=0; while (( i < 3 )); do echo $i; (( i++ )); done",
+        r#"let a=3+4 "b = a * 2"; echo $a $b"#,
+        "let 0; echo $?",
+        "let",
+    ];
+
+    let expected_output_sequence: Vec<&str> = vec!["1", "0", "0", "1", "2", "7 14", "1"];
+
+    let expected_error_sequence: Vec<&str> = vec!["let: expression expected"];
+
+    let (stdout, stderr) = run_shell(&input_sequence);
+    assert_eq!(stdout, expected_output_sequence);
+    assert_eq!(stderr, expected_error_sequence);
+}
@@ -0,0 +1,72 @@
+mod common;
+use common::run_shell;
+
+#[test]
+fn test_indexed_arrays() {
+    let input_sequence: Vec<&str> = vec![
+        r#"a=(x "y z" w)"#,
+        "echo ${#a[@]} ${a[1]} $a ${a[-1]}",
+        // Quoted `[@]` gives a field for every element
+        r#"for i in "${a[@]}"; do echo "<$i>"; done"#,
+        "a[5]=six; a+=(more); echo ${!a[@]}",
+        r#"echo "${a[*]}""#,
+        "echo ${a[@]:1:2}",
+        "unset 'a[1]'; echo ${!a[@]}",
+        r#"e=(); for i in "${e[@]}"; do echo never; done; echo "count ${#e[@]}""#,
+        "i=2; b=(zero one two three); echo ${b[i]} ${b[$i+1]} $((b[1] + 1)) ${#b[3]}",
+        "c=(1 2 3); c[1]+=0; echo ${c[@]} ${c[@]/2/two}",
+        "f() { local arr=(p q); echo ${arr[@]}; }; f; echo \"[${arr[@]}]\"",
+        "[[ foo123 =~ ^([a-z]+)([0-9]+) ]]; echo ${BASH_REMATCH[1]} ${BASH_REMATCH[2]}",
+        "declare -p c",
+    ];
+
+    let expected_output_sequence: Vec<&str> = vec![
+        "3 y z x w",
+        "<x>",
+        "<y z>",
+        "<w>",
+        "0 1 2 5 6",
+        "x y z w six more",
+        "y z w",
+        "0 2 5 6",
+        "count 0",
+        "two three 1 5",
+        "1 20 3 1 two0 3",
+        "p q",
+        "[]",
+        "foo 123",
+        r#"declare -a c=([0]="1" [1]="20" [2]="3")"#,
+    ];
+
+    let (stdout, stderr) = run_shell(&input_sequence);
+    assert_eq!(stdout, expected_output_sequence);
+    assert!(stderr.is_empty());
+}
+
+#[test]
+fn test_associative_arrays() {
+    let input_sequence: Vec<&str> = vec![
+        "declare -A m",
+        r#"m[one]=1; m["two words"]=2"#,
+        "echo ${m[one]} ${m[two words]} ${#m[@]}",
+        r#"for k in "${!m[@]}"; do echo "$k=${m[$k]}"; done"#,
+        r#"declare -A n=([a]=1 [b]="2 3"); echo ${n[b]} ${n[nope]:-default}"#,
+        "unset 'n[a]'; declare -p n",
+        "n=(oops)",
+    ];
+
+    let expected_output_sequence: Vec<&str> = vec![
+        "1 2 2",
+        "one=1",
+        "two words=2",
+        "2 3 default",
+        r#"declare -A n=([b]="2 3" )"#,
+    ];
+
+    let expected_error_sequence: Vec<&str> =
+        vec!["n: must use subscript when assigning associative array"];
+
+    let (stdout, stderr) = run_shell(&input_sequence);
+    assert_eq!(stdout, expected_output_sequence);
+    assert_eq!(stderr, expected_error_sequence);
+}
@@ -0,0 +1,56 @@
+mod common;
+use common::run_shell;
+
+#[test]
+fn test_substring_and_replace() {
+    let input_sequence: Vec<&str> = vec![
+        r#"s="héllo wörld""#,
+        // Offsets count characters, not bytes
+        r#"echo "${s:1}" "${s:1:3}" "${s: -3}" "${s: -5:2}" "${s:2:-2}" "[${s:20}]""#,
+        r#"echo "${s/l/L}" "${s//l/L}" "${s/#hé/HE}" "${s/%d/D}" "${s//o}" "${s/#/>}""#,
+        r#"path=a/b/c; echo ${path//\//:} "${path//"/"/_}" ${path/\/*/}"#,
+        r#"args() { echo "${@:2}" "|" "${@: -1}" "|" "${@/a/X}"; }; args apple banana cherry"#,
+        r#"echo "${s:1:-20}""#,
+    ];
+
+    let expected_output_sequence: Vec<&str> = vec![
+        "éllo wörld éll rld wö llo wör []",
+        "héLlo wörld héLLo wörLd HEllo wörld héllo wörlD héll wörld >héllo wörld",
+        "a:b:c a_b_c a",
+        "banana cherry | cherry | Xpple bXnana cherry",
+    ];
+
+    let expected_error_sequence: Vec<&str> = vec!["-20: substring expression < 0"];
+
+    let (stdout, stderr) = run_shell(&input_sequence);
+    assert_eq!(stdout, expected_output_sequence);
+    assert_eq!(stderr, expected_error_sequence);
+}
+
+#[test]
+fn test_case_indirection_and_quoting() {
+    let input_sequence: Vec<&str> = vec![
+        r#"s="héllo wörld" upper=ÉCOLE"#,
+        r#"echo "${s^}" "${s^^}" "${upper,}" "${upper,,}" "${s^^[lo]}""#,
+        "ref=s; echo ${!ref} ${!ref:0:2} ${!ref^^}",
+        r#"myvar_a=1 myvar_b=2; echo "${!myvar*}""#,
+        r#"for name in "${!myvar@}"; do echo $name; done"#,
+        r#"q="it's here"; echo ${q@Q} [${unset@Q}]"#,
+        "echo ${!unset}",
+    ];
+
+    let expected_output_sequence: Vec<&str> = vec![
+        "Héllo wörld HÉLLO WÖRLD éCOLE école héLLO wörLd",
+        "héllo wörld hé HÉLLO WÖRLD",
+        "myvar_a myvar_b",
+        "myvar_a",
+        "myvar_b",
+        r"'it'\''s here' []",
+    ];
+
+    let expected_error_sequence: Vec<&str> = vec!["unset: invalid indirect expansion"];
+
+    let (stdout, stderr) = run_shell(&input_sequence);
+    assert_eq!(stdout, expected_output_sequence);
+    assert_eq!(stderr, expected_error_sequence);
+}
@@ -0,0 +1,77 @@
+use std::io::{BufRead, Write};
+use std::process::{Command, Stdio};
+
+mod common;
+use common::{binary_path, run_shell};
+
+#[test]
+fn test_comments() {
+    let input_sequence: Vec<&str> = vec![
+        "echo hi # note",
+        "# a whole line",
+        // Only a `#` starting a word begins a comment
+        r##"echo a#b '#q' "#d" \#e"##,
+        "x=abc #; echo hidden",
+        "echo $# ${#x}",
+        "if true; then # the condition",
+        "  echo yes # inside",
+        "fi",
+    ];
+
+    let expected_output_sequence: Vec<&str> = vec!["hi", "a#b #q #d #e", "0 3", "yes"];
+
+    let (stdout, stderr) = run_shell(&input_sequence);
+    assert_eq!(stdout, expected_output_sequence);
+    assert!(stderr.is_empty());
+}
+
+#[test]
+fn test_interactive_comments_option() {
+    let input_sequence: Vec<&str> = vec![
+        "shopt interactive_comments",
+        "shopt -u interactive_comments",
+        // Commands which are not typed by the user always have comments
+        "echo after # still a comment",
+        "shopt -p interactive_comments",
+        "shopt -q interactive_comments; echo $?",
+        "shopt -s interactive_comments",
+        "echo back # a comment again",
+        "shopt nope; echo $?",
+    ];
+
+    let expected_output_sequence: Vec<&str> = vec![
+        "interactive_comments\ton",
+        "after",
+        "shopt -u interactive_comments",
+        "1",
+        "back",
+        "1",
+    ];
+
+    let (stdout, stderr) = run_shell(&input_sequence);
+    assert_eq!(stdout, expected_output_sequence);
+    assert_eq!(stderr, vec!["shopt: nope: invalid shell option name"]);
+}
+
+#[test]
+fn test_interactive_comments_off() {
+    // Lines read by an interactive shell keep their `#` words once the option is unset
+    let mut child = Command::new(binary_path())
+        .args(["--norc", "-i"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("failed to start child process");
+
+    let stdin = child.stdin.as_mut().expect("failed to open stdin");
+    stdin
+        .write_all(b"shopt -u interactive_comments\necho a # b\nshopt -s interactive_comments\necho c # d\nexit\n")
+        .unwrap();
+
+    let output = child.wait_with_output().expect("failed to read stdout");
+    assert!(output.status.success());
+    let stdout: Vec<String> = output.stdout.lines().map(|x| x.unwrap()).collect();
+    assert_eq!(stdout, vec!["a # b", "c"]);
+    assert!(output.stderr.is_empty());
+}
@@ -0,0 +1,62 @@
+// Each test file only uses some of the helpers
+#![allow(dead_code)]
+
+use std::env;
+use std::io::{BufRead, Write};
+use std::process::{Command, Stdio};
+
+pub fn binary_path() -> String {
+    env::var("CARGO_MANIFEST_DIR").unwrap()
+        + "/target/debug/"
+        + env::var("CARGO_PKG_NAME").unwrap().as_ref()
+}
+
+// Run the shell with the arguments and input, returning its exit status, stdout and stderr lines
+pub fn run_with_args(args: &[&str], input: &str) -> (i32, Vec<String>, Vec<String>) {
+    let mut child = Command::new(binary_path())
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("failed to start child process");
+
+    child
+        .stdin
+        .as_mut()
+        .expect("failed to open stdin")
+        .write_all(input.as_bytes())
+        .unwrap();
+
+    let output = child.wait_with_output().expect("failed to read stdout");
+    let stdout = output.stdout.lines().map(|x| x.unwrap()).collect();
+    let stderr = output.stderr.lines().map(|x| x.unwrap()).collect();
+    (output.status.code().unwrap(), stdout, stderr)
+}
+
+// Run the inputs through the shell and return its stdout and stderr lines
+pub fn run_shell(input_sequence: &[&str]) -> (Vec<String>, Vec<String>) {
+    let mut child = Command::new(binary_path())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("failed to start child process");
+
+    let stdin = child.stdin.as_mut().expect("failed to open stdin");
+    // Pass all the inputs from the inputs
+    // Then submit `exit 0` at the end to exit from our shell, whatever the status of the last input
+    for input in input_sequence.iter() {
+        stdin
+            .write_all((input.to_string() + "\n").as_bytes())
+            .unwrap();
+    }
+    stdin.write_all(b"exit 0\n").unwrap();
+
+    let output = child.wait_with_output().expect("failed to read stdout");
+    assert!(output.status.success());
+
+    let stdout = output.stdout.lines().map(|x| x.unwrap()).collect();
+    let stderr = output.stderr.lines().map(|x| x.unwrap()).collect();
+    (stdout, stderr)
+}
@@ -0,0 +1,45 @@
+mod common;
+use common::run_shell;
+
+#[test]
+fn test_conditional_comparisons() {
+    let input_sequence: Vec<&str> = vec![
+        // Operands are not split, and the right side of `==` is a pattern unless quoted
+        r#"x="a b"; [[ $x == "a b" ]]; echo $?"#,
+        "[[ $x == a* ]]; echo $?",
+        r#"[[ $x == "a*" ]]; echo $?"#,
+        "[[ $x != a* ]]; echo $?",
+        "[[ abc < abd && ! 3 < 10 ]]; echo $?",
+        // Integer operands are arithmetic expressions
+        "n=4; [[ n+1 -eq 5 && 3 -lt 10 ]]; echo $?",
+        "[[ -z '' && -n x && $unset ]]; echo $?",
+        "[[ ( a == b || -n x ) && -v n ]]; echo $?",
+    ];
+
+    let expected_output_sequence: Vec<&str> = vec!["0", "0", "1", "1", "0", "0", "1", "0"];
+
+    let (stdout, stderr) = run_shell(&input_sequence);
+    assert_eq!(stdout, expected_output_sequence);
+    assert!(stderr.is_empty());
+}
+
+#[test]
+fn test_conditional_files_and_regex() {
+    let input_sequence: Vec<&str> = vec![
+        "[[ -f Cargo.toml && -d src && -s Cargo.toml && -r Cargo.toml ]]; echo $?",
+        "[[ -e nonexistent || -d Cargo.toml ]]; echo $?",
+        "[[ Cargo.toml -ef ./Cargo.toml ]]; echo $?",
+        // Parentheses and `|` belong to the regular expression
+        "[[ foo123bar =~ ^([a-z]+)([0-9]+) ]]; echo $? $BASH_REMATCH",
+        "[[ abc =~ x|b ]]; echo $?",
+        // Quoted parts match literally
+        r#"[[ abc =~ "a.c" ]]; echo $?"#,
+        "[[ abc =~ a( ]]; echo $?",
+    ];
+
+    let expected_output_sequence: Vec<&str> = vec!["0", "1", "0", "0 foo123", "0", "1", "2"];
+
+    let (stdout, stderr) = run_shell(&input_sequence);
+    assert_eq!(stdout, expected_output_sequence);
+    assert!(stderr.is_empty());
+}
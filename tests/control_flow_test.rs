@@ -0,0 +1,68 @@
+mod common;
+use common::run_shell;
+
+#[test]
+fn test_if_and_loops() {
+    let input_sequence: Vec<&str> = vec![
+        "if false; then echo no; elif true; then echo elif; else echo else; fi",
+        "for x in a b c; do if test $x = b; then continue; fi; echo $x; done",
+        "for x in 1 2; do for y in a b; do if test $y = b; then break 2; fi; echo $x$y; done; done",
+        "until true; do echo never; done; echo after",
+        r#"n=; while test -z "$n"; do n=set; echo looped; done"#,
+        "break",
+    ];
+
+    let expected_output_sequence: Vec<&str> = vec!["elif", "a", "c", "1a", "after", "looped"];
+
+    let expected_error_sequence: Vec<&str> =
+        vec!["break: only meaningful in a `for', `while', or `until' loop"];
+
+    let (stdout, stderr) = run_shell(&input_sequence);
+    assert_eq!(stdout, expected_output_sequence);
+    assert_eq!(stderr, expected_error_sequence);
+}
+
+#[test]
+fn test_lists_and_groups() {
+    let input_sequence: Vec<&str> = vec![
+        "true && echo and; false || echo or; false && echo skipped",
+        "! true || echo negated",
+        "{ echo grouped; echo twice; } > /tmp/control_flow_test.txt",
+        "cat /tmp/control_flow_test.txt",
+    ];
+
+    let expected_output_sequence: Vec<&str> = vec!["and", "or", "negated", "grouped", "twice"];
+
+    let (stdout, stderr) = run_shell(&input_sequence);
+    assert_eq!(stdout, expected_output_sequence);
+    assert!(stderr.is_empty());
+}
+
+#[test]
+fn test_null_true_and_false() {
+    let input_sequence: Vec<&str> = vec![
+        "for i in 1; do :; done; echo $?",
+        "n=0; while :; do n=$((n + 1)); if test $n = 3; then break; fi; done; echo $n",
+        ": ignored arguments > /dev/null && echo null",
+        "false; echo $?; true; echo $?",
+        // They do not depend on `PATH`
+        "PATH=; true && ! false && echo builtins",
+        "type : true false",
+    ];
+
+    let expected_output_sequence: Vec<&str> = vec![
+        "0",
+        "3",
+        "null",
+        "1",
+        "0",
+        "builtins",
+        ": is a shell builtin",
+        "true is a shell builtin",
+        "false is a shell builtin",
+    ];
+
+    let (stdout, stderr) = run_shell(&input_sequence);
+    assert_eq!(stdout, expected_output_sequence);
+    assert!(stderr.is_empty());
+}
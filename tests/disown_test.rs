@@ -0,0 +1,74 @@
+use std::env;
+use std::fs;
+
+mod common;
+use common::run_with_args;
+
+#[test]
+fn test_disown_jobs() {
+    let commands = r#"sleep 1 & sleep 1 & disown -h %1; jobs
+disown; jobs; wait $! 2>/dev/null; echo waited $?
+sleep 1 & disown -ar; jobs; disown %5; disown 123; disown"#;
+    let (status, stdout, stderr) = run_with_args(&["-c", commands], "");
+    assert_eq!(status, 1);
+    assert_eq!(
+        stdout,
+        vec![
+            "[1]-  Running                 sleep 1 &",
+            "[2]+  Running                 sleep 1 &",
+            "[1]+  Running                 sleep 1 &",
+            "waited 127",
+        ]
+    );
+    assert_eq!(
+        stderr,
+        vec![
+            "disown: %5: no such job",
+            "disown: 123: no such job",
+            "disown: current: no such job",
+        ]
+    );
+}
+
+#[test]
+fn test_exit_with_jobs() {
+    // Stopped jobs make the first `exit` fail, the second one then exits and the jobs get SIGHUP
+    let log = env::temp_dir().join(format!("shell-hangup-{}", std::process::id()));
+    let input = format!(
+        r#"sh -c 'trap "echo stopped job >> {log}; exit" HUP; kill -STOP $$; sleep 5' &
+sh -c 'trap "echo running job >> {log}; exit" HUP; sleep 1' &
+sh -c 'trap "echo disowned job >> {log}; exit" HUP; sleep 0.5' &
+disown -h %3; sleep 0.2
+exit
+exit 3
+echo never
+"#,
+        log = log.display()
+    );
+    let (status, stdout, stderr) = run_with_args(&["-i", "--norc"], &input);
+    assert_eq!(status, 3);
+    assert!(stdout.is_empty());
+    assert!(stderr.contains(&"There are stopped jobs.".to_owned()));
+    std::thread::sleep(std::time::Duration::from_millis(300));
+    let mut lines: Vec<String> = fs::read_to_string(&log)
+        .unwrap()
+        .lines()
+        .map(|line| line.to_owned())
+        .collect();
+    lines.sort();
+    assert_eq!(lines, vec!["running job", "stopped job"]);
+    fs::remove_file(&log).unwrap();
+
+    // Other shells leave their jobs running, unless `huponexit` is set
+    let commands = format!(
+        r#"sh -c 'trap "echo hung up >> {log}; exit" HUP; sleep 1' & sleep 0.2"#,
+        log = log.display()
+    );
+    let (status, _, _) = run_with_args(&["-c", &commands], "");
+    assert_eq!(status, 0);
+    let (status, _, _) = run_with_args(&["-c", &format!("shopt -s huponexit; {}", commands)], "");
+    assert_eq!(status, 0);
+    std::thread::sleep(std::time::Duration::from_millis(300));
+    assert_eq!(fs::read_to_string(&log).unwrap(), "hung up\n");
+    fs::remove_file(&log).unwrap();
+}
@@ -0,0 +1,56 @@
+mod common;
+use common::run_shell;
+
+#[test]
+fn test_multibyte_words() {
+    let input_sequence: Vec<&str> = vec![
+        r#"echo "héllo wörld" ünï'cödé'"#,
+        "x=héllo",
+        "echo ${#x} ${x:1:3} ${x#h?} ${x%?o} ${x/é/E}",
+        "[[ $x == h?llo ]] && echo matched",
+        r#"IFS=é; y="aébéc"; echo $y"#,
+    ];
+
+    let expected_output_sequence: Vec<&str> = vec![
+        "héllo wörld ünïcödé",
+        "5 éll llo hél hEllo",
+        "matched",
+        "a b c",
+    ];
+
+    let (stdout, stderr) = run_shell(&input_sequence);
+    assert_eq!(stdout, expected_output_sequence);
+    assert!(stderr.is_empty());
+}
+
+#[test]
+fn test_non_utf8_bytes() {
+    let input_sequence: Vec<&str> = vec![
+        // Bytes which are not UTF-8 reach commands and files unchanged
+        r"printf '%s' $'\xff\xfe' | od -An -tx1",
+        r"echo $'a\xffb' | od -An -tx1",
+        r"x=$'a\xffb'; echo ${#x}",
+        r"cd /tmp && echo content > $'shell-test-\xff' && cat $'shell-test-\xff'",
+        r"[[ -f $'shell-test-\xff' ]] && echo exists; rm $'shell-test-\xff'",
+        // Bytes making up valid UTF-8 join into characters
+        r"echo $'\xc3\xa9'",
+        // Even the last characters of Unicode, next to the ones carrying bytes inside the shell
+        "echo \u{10FFFD} | od -An -tx1",
+        r"echo $'\U10FFFD' | od -An -tx1",
+    ];
+
+    let expected_output_sequence: Vec<&str> = vec![
+        " ff fe",
+        " 61 ff 62 0a",
+        "3",
+        "content",
+        "exists",
+        "é",
+        " f4 8f bf bd 0a",
+        " f4 8f bf bd 0a",
+    ];
+
+    let (stdout, stderr) = run_shell(&input_sequence);
+    assert_eq!(stdout, expected_output_sequence);
+    assert!(stderr.is_empty());
+}
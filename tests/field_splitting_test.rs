@@ -0,0 +1,59 @@
+mod common;
+use common::run_shell;
+
+#[test]
+fn test_default_field_splitting() {
+    let input_sequence: Vec<&str> = vec![
+        r#"show() { for i in "$@"; do echo "<$i>"; done; }"#,
+        "count() { echo $#; }",
+        r#"x="  a   b  "; show $x"#,
+        // Empty unquoted expansions vanish, quoted ones stay
+        r#"e=; count $e; count $e """#,
+        r#"f() { show $@; }; f "a b" c"#,
+        r#"y="a b"; show pre$y"q r""#,
+        // Assignments and `[[ ]]` are never split
+        r#"z=$x; echo "[$z]""#,
+        r#"[[ $x == "  a   b  " ]] && echo unsplit"#,
+    ];
+
+    let expected_output_sequence: Vec<&str> = vec![
+        "<a>",
+        "<b>",
+        "0",
+        "1",
+        "<a>",
+        "<b>",
+        "<c>",
+        "<prea>",
+        "<bq r>",
+        "[  a   b  ]",
+        "unsplit",
+    ];
+
+    let (stdout, stderr) = run_shell(&input_sequence);
+    assert_eq!(stdout, expected_output_sequence);
+    assert!(stderr.is_empty());
+}
+
+#[test]
+fn test_custom_ifs() {
+    let input_sequence: Vec<&str> = vec![
+        r#"show() { for i in "$@"; do echo "<$i>"; done; }"#,
+        // Other characters than blanks end a field even if it is empty
+        r#"IFS=:; y="a::b:"; show $y"#,
+        r#"IFS=" :"; y="a : b  c:"; show $y"#,
+        // An empty IFS disables splitting
+        r#"IFS=; y="a b"; show $y"#,
+        r#"g() { IFS=,; echo "$*"; }; g "a b" c"#,
+        r#"arr=(1 2 3); echo "${arr[*]}""#,
+        "unset IFS; y='a b'; show $y",
+    ];
+
+    let expected_output_sequence: Vec<&str> = vec![
+        "<a>", "<>", "<b>", "<a>", "<b>", "<c>", "<a b>", "a b,c", "1,2,3", "<a>", "<b>",
+    ];
+
+    let (stdout, stderr) = run_shell(&input_sequence);
+    assert_eq!(stdout, expected_output_sequence);
+    assert!(stderr.is_empty());
+}
@@ -0,0 +1,84 @@
+mod common;
+use common::run_shell;
+
+#[test]
+fn test_function_definition() {
+    let input_sequence: Vec<&str> = vec![
+        r#"greet() { echo "hello $1"; }"#,
+        "greet world",
+        "function twice { echo $1$1; }",
+        "twice ab",
+        // Positional parameters are restored after the call
+        "show() { echo $# $@; }",
+        "show a b c",
+        "for x in one two; do show $x; done",
+        "type greet",
+    ];
+
+    let expected_output_sequence: Vec<&str> = vec![
+        "hello world",
+        "abab",
+        "3 a b c",
+        "1 one",
+        "1 two",
+        "greet is a function",
+        r#"greet() { echo "hello $1"; }"#,
+    ];
+
+    let (stdout, stderr) = run_shell(&input_sequence);
+    assert_eq!(stdout, expected_output_sequence);
+    assert!(stderr.is_empty());
+}
+
+#[test]
+fn test_local_and_return() {
+    let input_sequence: Vec<&str> = vec![
+        // `local` is seen by called functions but not by the caller
+        r#"show_x() { echo "x is $x"; }"#,
+        "outer() { local x=inner; show_x; }",
+        "x=global",
+        "outer",
+        "show_x",
+        "f() { return 3; echo unreachable; }",
+        "f; echo $?",
+        "g() { false; return; }",
+        "g; echo $?",
+        "first() { for i in 1 2 3; do if test $i = 2; then return 7; fi; echo $i; done; }",
+        "first; echo $?",
+        "local y=1",
+        "return",
+    ];
+
+    let expected_output_sequence: Vec<&str> = vec!["x is inner", "x is global", "3", "1", "1", "7"];
+
+    let expected_error_sequence: Vec<&str> = vec![
+        "local: can only be used in a function",
+        "return: can only `return' from a function or sourced script",
+    ];
+
+    let (stdout, stderr) = run_shell(&input_sequence);
+    assert_eq!(stdout, expected_output_sequence);
+    assert_eq!(stderr, expected_error_sequence);
+}
+
+#[test]
+fn test_command_and_builtin() {
+    let input_sequence: Vec<&str> = vec![
+        // Functions take precedence over builtins
+        r#"echo() { builtin echo "wrapped $1"; }"#,
+        "echo hi",
+        "command echo plain",
+        "unset -f echo",
+        "echo back",
+        "builtin ls",
+        "command -v cd",
+    ];
+
+    let expected_output_sequence: Vec<&str> = vec!["wrapped hi", "plain", "back", "cd"];
+
+    let expected_error_sequence: Vec<&str> = vec!["builtin: ls: not a shell builtin"];
+
+    let (stdout, stderr) = run_shell(&input_sequence);
+    assert_eq!(stdout, expected_output_sequence);
+    assert_eq!(stderr, expected_error_sequence);
+}
@@ -0,0 +1,69 @@
+use std::env;
+use std::fs;
+
+mod common;
+use common::run_with_args;
+
+#[test]
+fn test_set_options() {
+    let commands = r#"echo $-; set -o | grep -E 'errexit|nounset'
+set +e -f -- a "b c"; echo $- $# "$2"
+set +o | grep noglob; set --; echo $#
+set -q; echo $?; set -o bogus; echo $?"#;
+    let (status, stdout, stderr) = run_with_args(&["-eu", "-c", commands], "");
+    assert_eq!(status, 0);
+    assert_eq!(
+        stdout,
+        vec![
+            "euc",
+            "errexit        \ton",
+            "nounset        \ton",
+            "fuc 2 b c",
+            "set -o noglob",
+            "0",
+            "2",
+            "2",
+        ]
+    );
+    assert_eq!(
+        stderr,
+        vec![
+            "set: -q: invalid option",
+            "set: usage: set [-abefux] [-o option-name] [--] [-] [arg ...]",
+            "set: bogus: invalid option name",
+        ]
+    );
+
+    let (status, stdout, stderr) = run_with_args(&["-o", "bogus", "-c", "echo never"], "");
+    assert_eq!(status, 2);
+    assert!(stdout.is_empty());
+    assert_eq!(stderr, vec!["bogus: invalid option name"]);
+}
+
+#[test]
+fn test_invocation_flags() {
+    // `-i` makes the shell interactive even when reading from a pipe, so the rc file is read
+    let home = env::temp_dir().join(format!("shell-invocation-test-{}", std::process::id()));
+    fs::create_dir_all(&home).unwrap();
+    let rc_file = home.join("rc");
+    fs::write(&rc_file, "echo rc loaded\n").unwrap();
+    let rc_file = rc_file.to_str().unwrap();
+    let (status, stdout, _) = run_with_args(&["-i", "--rcfile", rc_file], "echo $-\n");
+    assert_eq!(status, 0);
+    assert_eq!(stdout, vec!["rc loaded", "is"]);
+    fs::remove_dir_all(home).unwrap();
+
+    let (status, stdout, _) = run_with_args(&["--version"], "");
+    assert_eq!(status, 0);
+    assert_eq!(stdout, vec!["codecrafters-shell, version 0.1.0"]);
+
+    // Options end at `--`, after which `-c` is the name of a script
+    let (status, stdout, stderr) = run_with_args(&["--", "-c"], "");
+    assert_eq!(status, 127);
+    assert!(stdout.is_empty());
+    assert_eq!(stderr, vec!["-c: No such file or directory"]);
+
+    let (status, _, stderr) = run_with_args(&["-q"], "");
+    assert_eq!(status, 2);
+    assert_eq!(stderr, vec!["-q: invalid option"]);
+}
@@ -0,0 +1,60 @@
+mod common;
+use common::run_with_args;
+
+#[test]
+fn test_kill_jobs_and_pids() {
+    let commands = r#"sleep 5 & sleep 5 & kill %-; wait %1; echo first $?
+kill -s KILL %%; wait $!; echo second $?
+sh -c 'sleep 5; echo never' & kill -n 15 -- -$!; wait; echo group $?
+trap 'echo got USR1' USR1; kill -USR1 $$; kill -0 $$; echo alive $?
+kill -BOGUS $$; kill abc; kill %3; kill; echo usage $?"#;
+    let (status, stdout, stderr) = run_with_args(&["-c", commands], "");
+    assert_eq!(status, 0);
+    assert_eq!(
+        stdout,
+        vec![
+            "first 143",
+            "second 137",
+            "group 0",
+            "got USR1",
+            "alive 0",
+            "usage 2"
+        ]
+    );
+    // The job killed by SIGKILL is reported with its pid
+    assert!(stderr[0].ends_with(" Killed                  sleep 5"));
+    assert_eq!(
+        stderr[1..],
+        vec![
+            "kill: BOGUS: invalid signal specification",
+            "kill: abc: arguments must be process or job IDs",
+            "kill: %3: no such job",
+            "kill: usage: kill [-s sigspec | -n signum | -sigspec] pid | jobspec ... or kill -l [sigspec]",
+        ]
+    );
+}
+
+#[test]
+fn test_list_signals() {
+    let (status, stdout, _) =
+        run_with_args(&["-c", "kill -l 9 TERM sigint 143; kill -l | head -1"], "");
+    assert_eq!(status, 0);
+    assert_eq!(
+        stdout,
+        vec![
+            "KILL",
+            "15",
+            "2",
+            "TERM",
+            " 1) SIGHUP\t 2) SIGINT\t 3) SIGQUIT\t 4) SIGILL\t 5) SIGTRAP",
+        ]
+    );
+
+    let (status, stdout, stderr) = run_with_args(&["-c", "trap -l | head -1; kill -l 200"], "");
+    assert_eq!(status, 1);
+    assert_eq!(
+        stdout,
+        vec![" 1) SIGHUP\t 2) SIGINT\t 3) SIGQUIT\t 4) SIGILL\t 5) SIGTRAP"]
+    );
+    assert_eq!(stderr, vec!["kill: 200: invalid signal specification"]);
+}
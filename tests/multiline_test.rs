@@ -0,0 +1,98 @@
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+mod common;
+use common::{binary_path, run_shell};
+
+#[test]
+fn test_continuation_lines() {
+    let input_sequence: Vec<&str> = vec![
+        // An open quote keeps its newline
+        "echo 'first",
+        "second'",
+        "if true",
+        "then echo yes",
+        "fi",
+        "echo one |",
+        "tr o 0",
+        "false ||",
+        "echo or",
+        // A trailing backslash joins the lines
+        "echo con\\",
+        "tinued",
+        "f() {",
+        "  echo in f",
+        "}",
+        "f",
+    ];
+
+    let expected_output_sequence: Vec<&str> =
+        vec!["first", "second", "yes", "0ne", "or", "continued", "in f"];
+
+    let (stdout, stderr) = run_shell(&input_sequence);
+    assert_eq!(stdout, expected_output_sequence);
+    assert!(stderr.is_empty());
+}
+
+#[test]
+fn test_here_documents() {
+    let input_sequence: Vec<&str> = vec![
+        "x=world",
+        "cat <<END",
+        "hello $x \"$((1 + 2))\"",
+        "\\$x",
+        "END",
+        // A quoted delimiter turns off expansion
+        "cat <<'END'; echo after",
+        "hello $x",
+        "END",
+        // `<<-` strips the leading tabs
+        "cat <<-END",
+        "\tindented",
+        "\tEND",
+        "tr a-z A-Z <<A; cat <<B",
+        "upper",
+        "A",
+        "second",
+        "B",
+    ];
+
+    let expected_output_sequence: Vec<&str> = vec![
+        "hello world \"3\"",
+        "$x",
+        "hello $x",
+        "after",
+        "indented",
+        "UPPER",
+        "second",
+    ];
+
+    let (stdout, stderr) = run_shell(&input_sequence);
+    assert_eq!(stdout, expected_output_sequence);
+    assert!(stderr.is_empty());
+}
+
+#[test]
+fn test_continuation_prompt() {
+    // The editor writes its prompts to stdout on a dumb terminal, even when the input is piped
+    let mut child = Command::new(binary_path())
+        .args(["--norc", "-i"])
+        .env("TERM", "dumb")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("failed to start child process");
+
+    let stdin = child.stdin.as_mut().expect("failed to open stdin");
+    stdin
+        .write_all(b"if true\nthen echo yes\nfi\nPS2='$x>> ' x=cont\necho 'a\nb'\nexit\n")
+        .unwrap();
+
+    let output = child.wait_with_output().expect("failed to read stdout");
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "$ > > yes\n$ $ cont>> a\nb\n$ "
+    );
+}
@@ -0,0 +1,33 @@
+mod common;
+use common::run_with_args;
+
+#[test]
+fn test_piped_input_has_no_prompt() {
+    let input = "echo one\nif true\nthen echo two\nfi\necho \"three\nfour\"\n";
+    let (status, stdout, stderr) = run_with_args(&[], input);
+    // Nothing but the output of the commands, without any `$ ` prompt
+    assert_eq!(status, 0);
+    assert_eq!(stdout, vec!["one", "two", "three", "four"]);
+    assert!(stderr.is_empty());
+}
+
+#[test]
+fn test_end_of_piped_input() {
+    // The status at the end of the input is the one of the last command
+    let (status, stdout, _) = run_with_args(&[], "echo before\nfalse\n");
+    assert_eq!(status, 1);
+    assert_eq!(stdout, vec!["before"]);
+
+    // The last line does not need a newline
+    let (status, stdout, _) = run_with_args(&[], "echo last");
+    assert_eq!(status, 0);
+    assert_eq!(stdout, vec!["last"]);
+
+    let (status, stdout, stderr) = run_with_args(&[], "echo ok\necho \"open\n");
+    assert_eq!(status, 2);
+    assert_eq!(stdout, vec!["ok"]);
+    assert_eq!(
+        stderr,
+        vec!["unexpected EOF while looking for matching `\"'"]
+    );
+}
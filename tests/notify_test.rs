@@ -0,0 +1,65 @@
+mod common;
+use common::run_with_args;
+
+// The lines like `[1] 1234` printed when a job starts, whose pid changes every run
+fn is_job_start(line: &str) -> bool {
+    line.split_once("] ")
+        .is_some_and(|(id, pid)| id.starts_with('[') && pid.bytes().all(|b| b.is_ascii_digit()))
+}
+
+#[test]
+fn test_notify_interactive() {
+    let input = r#"sleep 0.1 &
+sleep 0.3
+false &
+sleep 0.3
+sleep 5 &
+kill -KILL %1
+sleep 0.3
+sleep 0.1 &
+sleep 0.3; jobs
+set -b
+{ sleep 0.1 & sleep 0.3; echo after; } 2>&1
+"#;
+    let (status, stdout, stderr) = run_with_args(&["--norc", "-i"], input);
+    assert_eq!(status, 0);
+    let stdout: Vec<String> = stdout
+        .into_iter()
+        .filter(|line| !is_job_start(line))
+        .collect();
+    assert_eq!(
+        stdout,
+        vec![
+            "[1]+  Done                    sleep 0.1",
+            "[1]+  Done                    sleep 0.1",
+            "after",
+        ]
+    );
+    let stderr: Vec<String> = stderr
+        .into_iter()
+        .filter(|line| !is_job_start(line))
+        .collect();
+    assert_eq!(
+        stderr,
+        vec![
+            "[1]+  Done                    sleep 0.1",
+            "[1]+  Exit 1                  false",
+            "[1]+  Killed                  sleep 5",
+        ]
+    );
+}
+
+#[test]
+fn test_notify_script() {
+    let commands = "sleep 5 & kill -KILL $!; wait; echo $!; sleep 5 & kill $!; wait; echo done";
+    let (status, stdout, stderr) = run_with_args(&["-c", commands], "");
+    assert_eq!(status, 0);
+    assert_eq!(stdout.len(), 2);
+    assert_eq!(stdout[1], "done");
+    // Only signals other than INT, TERM and PIPE are worth reporting
+    let pid: i32 = stdout[0].parse().unwrap();
+    assert_eq!(
+        stderr,
+        vec![format!("{:5} Killed                  sleep 5", pid)]
+    );
+}
@@ -0,0 +1,77 @@
+mod common;
+use common::{run_shell, run_with_args};
+
+#[test]
+fn test_default_values() {
+    let input_sequence: Vec<&str> = vec![
+        "empty=''",
+        // With the colon an empty value counts as missing, without it only an unset one does
+        r#"echo "${unset:-default}" "${unset-default}" "${empty:-default}" "[${empty-default}]""#,
+        r#"echo "${empty+set}" "[${unset+set}]" "[${empty:+alt}]" "${HOME:+alt}""#,
+        "echo ${new:=assigned} $new",
+        r#"echo "${unset:-"quoted  value"}""#,
+        "echo ${1:=positional}",
+    ];
+
+    let expected_output_sequence: Vec<&str> = vec![
+        "default default default []",
+        "set [] [] alt",
+        "assigned assigned",
+        "quoted  value",
+    ];
+
+    let expected_error_sequence: Vec<&str> = vec!["$1: cannot assign in this way"];
+
+    let (stdout, stderr) = run_shell(&input_sequence);
+    assert_eq!(stdout, expected_output_sequence);
+    assert_eq!(stderr, expected_error_sequence);
+}
+
+#[test]
+fn test_error_if_unset() {
+    // An interactive shell reports the error and goes on with the next command
+    let input = "empty=''\necho ${unset:?custom message}\necho $?\necho ${empty:?}\n";
+    let (status, stdout, stderr) = run_with_args(&["--norc", "-i"], input);
+    assert_eq!(status, 1);
+    assert_eq!(stdout, vec!["1"]);
+    assert_eq!(
+        stderr,
+        vec!["unset: custom message", "empty: parameter null or not set"]
+    );
+
+    // Any other shell exits, without running the rest of the script
+    let commands = "f() { : ${unset:?gone}; echo in f; }; f; echo after";
+    let (status, stdout, stderr) = run_with_args(&["-c", commands], "");
+    assert_eq!(status, 127);
+    assert!(stdout.is_empty());
+    assert_eq!(stderr, vec!["unset: gone"]);
+}
+
+#[test]
+fn test_length_and_pattern_removal() {
+    let input_sequence: Vec<&str> = vec![
+        "path=/usr/local/lib/file.tar.gz",
+        "echo ${#path} ${#unset}",
+        "echo ${path#*/} ${path##*/}",
+        "echo ${path%.*} ${path%%.*}",
+        "echo ${path%.[a-z][a-z]} ${path##*[[:digit:]]}",
+        // Quoted pattern characters match literally
+        r#"star='*'; echo ${path#$star} ${path#"$star"}"#,
+        r#"word="héllo wörld"; echo ${#word} ${word#h?} ${word%ö*}"#,
+        r#"strip() { echo "${@#-}"; }; strip -a -b c"#,
+    ];
+
+    let expected_output_sequence: Vec<&str> = vec![
+        "26 0",
+        "usr/local/lib/file.tar.gz file.tar.gz",
+        "/usr/local/lib/file.tar /usr/local/lib/file",
+        "/usr/local/lib/file.tar /usr/local/lib/file.tar.gz",
+        "/usr/local/lib/file.tar.gz /usr/local/lib/file.tar.gz",
+        "11 llo wörld héllo w",
+        "a b c",
+    ];
+
+    let (stdout, stderr) = run_shell(&input_sequence);
+    assert_eq!(stdout, expected_output_sequence);
+    assert!(stderr.is_empty());
+}
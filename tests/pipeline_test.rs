@@ -0,0 +1,35 @@
+mod common;
+use common::run_shell;
+
+#[test]
+fn test_pipelines() {
+    let input_sequence: Vec<&str> = vec![
+        "echo one two | tr a-z A-Z",
+        r#"printf 'b\na\n' | sort | head -n 1"#,
+        "{ echo a; echo b; } | grep -c .",
+        // The status is the one of the last command, which `!` negates
+        "! echo hi | grep -q bye && echo negated",
+    ];
+
+    let expected_output_sequence: Vec<&str> = vec!["ONE TWO", "a", "2", "negated"];
+
+    let (stdout, stderr) = run_shell(&input_sequence);
+    assert_eq!(stdout, expected_output_sequence);
+    assert!(stderr.is_empty());
+}
+
+#[test]
+fn test_subshell() {
+    let input_sequence: Vec<&str> = vec![
+        // Changes made in a subshell do not reach the parent shell
+        "x=outer; (x=inner; echo $x); echo $x",
+        "(exit 3); echo $?",
+        "(echo first; echo second) > /tmp/subshell_test.txt; cat /tmp/subshell_test.txt",
+    ];
+
+    let expected_output_sequence: Vec<&str> = vec!["inner", "outer", "3", "first", "second"];
+
+    let (stdout, stderr) = run_shell(&input_sequence);
+    assert_eq!(stdout, expected_output_sequence);
+    assert!(stderr.is_empty());
+}
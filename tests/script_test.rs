@@ -0,0 +1,72 @@
+use std::env;
+use std::fs;
+use std::io::BufRead;
+use std::os::unix::fs::PermissionsExt;
+use std::process::Command;
+
+mod common;
+use common::{binary_path, run_with_args};
+
+#[test]
+fn test_command_string() {
+    let (status, stdout, stderr) =
+        run_with_args(&["-c", r#"echo "$0 $# $1"; false"#, "name", "a b", "c"], "");
+    assert_eq!(status, 1);
+    assert_eq!(stdout, vec!["name 2 a b"]);
+    assert!(stderr.is_empty());
+
+    // Commands are read from the input with `-s`
+    let (status, stdout, stderr) = run_with_args(&["-s", "x", "y"], "echo $# $2\nexit 4\n");
+    assert_eq!(status, 4);
+    assert_eq!(stdout, vec!["2 y"]);
+    assert!(stderr.is_empty());
+
+    // A bare `exit` keeps the last status, and a status code wraps around
+    for (commands, expected_status) in [("false; exit", 1), ("exit 300", 44), ("exit -1", 255)] {
+        let (status, _, stderr) = run_with_args(&["-c", commands], "");
+        assert_eq!(status, expected_status, "{}", commands);
+        assert!(stderr.is_empty());
+    }
+    let (status, stdout, stderr) = run_with_args(&["-c", "exit abc; echo after"], "");
+    assert_eq!(status, 2);
+    assert!(stdout.is_empty());
+    assert_eq!(stderr, vec!["exit: abc: numeric argument required"]);
+}
+
+#[test]
+fn test_script_file() {
+    let script = env::temp_dir().join(format!("shell-script-test-{}", std::process::id()));
+    let source = [
+        format!("#!{}", binary_path()),
+        r#"echo "$# args: $@""#.to_owned(),
+        "# Functions and variables work like when typed".to_owned(),
+        "f() { echo in f; return 3; }".to_owned(),
+        "f; echo status $?".to_owned(),
+        "exit 5".to_owned(),
+        "echo unreachable".to_owned(),
+    ];
+    fs::write(&script, source.join("\n") + "\n").unwrap();
+    fs::set_permissions(&script, fs::Permissions::from_mode(0o755)).unwrap();
+    let script = script.to_str().unwrap();
+
+    let expected_stdout = vec!["2 args: a b c", "in f", "status 3"];
+    let (status, stdout, stderr) = run_with_args(&[script, "a", "b c"], "");
+    assert_eq!(status, 5);
+    assert_eq!(stdout, expected_stdout);
+    assert!(stderr.is_empty());
+
+    // Through the shebang line
+    let output = Command::new(script).args(["a", "b c"]).output().unwrap();
+    let stdout: Vec<String> = output.stdout.lines().map(|x| x.unwrap()).collect();
+    assert_eq!(output.status.code(), Some(5));
+    assert_eq!(stdout, expected_stdout);
+    fs::remove_file(script).unwrap();
+
+    let (status, stdout, stderr) = run_with_args(&["/nonexistent/script"], "");
+    assert_eq!(status, 127);
+    assert!(stdout.is_empty());
+    assert_eq!(
+        stderr,
+        vec!["/nonexistent/script: No such file or directory"]
+    );
+}
@@ -0,0 +1,57 @@
+use std::env;
+use std::fs;
+
+mod common;
+use common::run_with_args;
+
+#[test]
+fn test_errexit_and_pipefail() {
+    // Conditions, the left of `&&` and `||` and negated pipelines do not exit
+    let commands = r#"set -e
+if false; then :; fi; while false; do :; done
+false && true; ! true; false | true
+{ false || true; }; echo survived
+f() { false; echo ignored in a condition; }; if f; then echo done; fi
+set -o pipefail; (exit 2) | (exit 3) | true; echo unreachable"#;
+    let (status, stdout, stderr) = run_with_args(&["-c", commands], "");
+    assert_eq!(status, 3);
+    assert_eq!(stdout, vec!["survived", "ignored in a condition", "done"]);
+    assert!(stderr.is_empty());
+
+    let (status, stdout, _) = run_with_args(&["-e"], "echo a\n[[ a == b ]]\necho b\n");
+    assert_eq!(status, 1);
+    assert_eq!(stdout, vec!["a"]);
+}
+
+#[test]
+fn test_nounset_noglob_and_allexport() {
+    let commands = r#"set -u; echo "${unset-default}" "$@" $#
+[[ -o nounset ]] && echo on; echo $unset; echo unreachable"#;
+    let (status, stdout, stderr) = run_with_args(&["-c", commands], "");
+    assert_eq!(status, 1);
+    assert_eq!(stdout, vec!["default 0", "on"]);
+    assert_eq!(stderr, vec!["unset: unbound variable"]);
+
+    let directory = env::temp_dir().join(format!("shell-glob-test-{}", std::process::id()));
+    fs::create_dir_all(directory.join("dir")).unwrap();
+    for file in ["b.txt", "a.txt", ".hidden.txt", "dir/c.txt"] {
+        fs::write(directory.join(file), "").unwrap();
+    }
+    let commands = r#"cd "$1"; echo *.txt "*.txt" */*.txt; pattern='[ab].t?t'; echo $pattern
+echo none*; set -f; echo *.txt; set +f -a; exported=yes; env | grep ^exported"#;
+    let (status, stdout, stderr) =
+        run_with_args(&["-c", commands, "name", directory.to_str().unwrap()], "");
+    assert_eq!(status, 0);
+    assert_eq!(
+        stdout,
+        vec![
+            "a.txt b.txt *.txt dir/c.txt",
+            "a.txt b.txt",
+            "none*",
+            "*.txt",
+            "exported=yes",
+        ]
+    );
+    assert!(stderr.is_empty());
+    fs::remove_dir_all(directory).unwrap();
+}
@@ -0,0 +1,79 @@
+use std::env;
+
+mod common;
+use common::run_shell;
+
+#[test]
+fn test_source_runs_in_current_shell() {
+    let parent = env::temp_dir().canonicalize().unwrap();
+    let dir = parent.join(format!("shell-source-test-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let lines = [
+        r#"greeting="hello $1""#,
+        r#"greet() { echo "$greeting from greet"; }"#,
+        r#"echo "args: $# $@""#,
+        "cd ..",
+        // `return` stops the file with its status
+        "return 4",
+        "echo unreachable",
+    ];
+    std::fs::write(dir.join("lib.sh"), lines.join("\n")).unwrap();
+
+    let cd = format!("cd {}", dir.to_str().unwrap());
+    let input_sequence: Vec<&str> = vec![
+        &cd,
+        "source ./lib.sh world; echo status $?",
+        // Variables, functions and the working directory are kept, the arguments are not
+        "greet; pwd; echo $#",
+        &cd,
+        "f() { . ./lib.sh; echo after source in f; }",
+        "f",
+    ];
+
+    let expected_output_sequence: Vec<&str> = vec![
+        "args: 1 world",
+        "status 4",
+        "hello world from greet",
+        parent.to_str().unwrap(),
+        "0",
+        "args: 0 ",
+        "after source in f",
+    ];
+
+    let (stdout, stderr) = run_shell(&input_sequence);
+    std::fs::remove_dir_all(dir).unwrap();
+    assert_eq!(stdout, expected_output_sequence);
+    assert!(stderr.is_empty());
+}
+
+#[test]
+fn test_source_lookup_and_errors() {
+    let dir = env::temp_dir().join(format!("shell-source-path-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    // A file found in PATH does not need to be executable
+    std::fs::write(dir.join("setup.sh"), "echo found in path\n").unwrap();
+
+    let path = format!("PATH={}:$PATH . setup.sh", dir.to_str().unwrap());
+    let input_sequence: Vec<&str> = vec![
+        &path,
+        "source; echo $?",
+        ". nonexistent; echo $?",
+        "source /; echo $?",
+        "return; echo $?",
+    ];
+
+    let expected_output_sequence: Vec<&str> = vec!["found in path", "2", "1", "1", "1"];
+
+    let expected_error_sequence: Vec<&str> = vec![
+        "source: filename argument required",
+        "source: usage: source filename [arguments]",
+        "nonexistent: No such file or directory",
+        "source: /: is a directory",
+        "return: can only `return' from a function or sourced script",
+    ];
+
+    let (stdout, stderr) = run_shell(&input_sequence);
+    std::fs::remove_dir_all(dir).unwrap();
+    assert_eq!(stdout, expected_output_sequence);
+    assert_eq!(stderr, expected_error_sequence);
+}
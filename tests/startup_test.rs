@@ -0,0 +1,71 @@
+use std::env;
+use std::fs;
+use std::io::{BufRead, Write};
+use std::os::unix::process::CommandExt;
+use std::process::{Command, Stdio};
+
+mod common;
+use common::binary_path;
+
+// Run the shell as `argv0` with the arguments and input, in a home directory holding a `.profile`
+// Returns its stdout lines, leaving out the stderr which depends on the `/etc/profile` of the system
+fn run_login(argv0: &str, args: &[&str], input: &str) -> Vec<String> {
+    let home = env::temp_dir().join(format!("shell-home-{}-{}", std::process::id(), argv0));
+    fs::create_dir_all(&home).unwrap();
+    fs::write(home.join(".profile"), "echo profile loaded\nGREETING=hi\n").unwrap();
+
+    let mut child = Command::new(binary_path())
+        .arg0(argv0)
+        .args(args)
+        .env("HOME", &home)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("failed to start child process");
+
+    child
+        .stdin
+        .as_mut()
+        .expect("failed to open stdin")
+        .write_all(input.as_bytes())
+        .unwrap();
+
+    let output = child.wait_with_output().expect("failed to read stdout");
+    fs::remove_dir_all(home).unwrap();
+    output.stdout.lines().map(|x| x.unwrap()).collect()
+}
+
+#[test]
+fn test_login_profile() {
+    let stdout = run_login("shell", &["-l"], "echo $GREETING\n");
+    assert_eq!(stdout, vec!["profile loaded", "hi"]);
+
+    // A `-` in front of the program name also makes a login shell
+    let stdout = run_login("-shell", &[], "echo $GREETING\n");
+    assert_eq!(stdout, vec!["profile loaded", "hi"]);
+
+    let stdout = run_login(
+        "noprofile",
+        &["-l", "--noprofile"],
+        "echo ${GREETING-unset}\n",
+    );
+    assert_eq!(stdout, vec!["unset"]);
+}
+
+#[test]
+fn test_no_startup_files_without_login() {
+    // Shells which are neither login nor interactive read no startup file
+    let stdout = run_login(
+        "plain",
+        &["--rcfile", "/dev/null"],
+        "echo ${GREETING-unset}\n",
+    );
+    assert_eq!(stdout, vec!["unset"]);
+
+    let stdout = run_login("command", &["-c", "echo ${GREETING-unset} $0", "name"], "");
+    assert_eq!(stdout, vec!["unset name"]);
+
+    let stdout = run_login("invalid", &["--bogus"], "echo never\n");
+    assert!(stdout.is_empty());
+}
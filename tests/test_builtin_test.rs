@@ -0,0 +1,61 @@
+mod common;
+use common::run_shell;
+
+#[test]
+fn test_test_builtin() {
+    let input_sequence: Vec<&str> = vec![
+        "[ -f Cargo.toml ]; echo $?",
+        "test -d Cargo.toml; echo $?",
+        "[ -d src -a ! -s nonexistent ]; echo $?",
+        "[ 3 -lt 10 ]; echo $? ; [ 3 '<' 10 ]; echo $?",
+        r#"x=""; [ "$x" ]; echo $? ; [ -z "$x" ]; echo $?"#,
+        // With few arguments the count decides, so a lone `-n` is a non-empty string
+        "[ ]; echo $? ; [ -n ]; echo $? ; [ ! ]; echo $?",
+        r"[ \( a = b \) -o \( 1 -eq 1 \) ]; echo $?",
+        "test Cargo.toml -ef ./Cargo.toml; echo $?",
+        "type [",
+    ];
+
+    let expected_output_sequence: Vec<&str> = vec![
+        "0",
+        "1",
+        "0",
+        "0",
+        "1",
+        "1",
+        "0",
+        "1",
+        "0",
+        "0",
+        "0",
+        "0",
+        "[ is a shell builtin",
+    ];
+
+    let (stdout, stderr) = run_shell(&input_sequence);
+    assert_eq!(stdout, expected_output_sequence);
+    assert!(stderr.is_empty());
+}
+
+#[test]
+fn test_test_builtin_errors() {
+    let input_sequence: Vec<&str> = vec![
+        "[ a -eq 1 ]; echo $?",
+        "[ 1 = 1; echo $?",
+        "test a b; echo $?",
+        "[ a b c d e ]; echo $?",
+    ];
+
+    let expected_output_sequence: Vec<&str> = vec!["2", "2", "2", "2"];
+
+    let expected_error_sequence: Vec<&str> = vec![
+        "[: a: integer expression expected",
+        "[: missing `]'",
+        "test: a: unary operator expected",
+        "[: too many arguments",
+    ];
+
+    let (stdout, stderr) = run_shell(&input_sequence);
+    assert_eq!(stdout, expected_output_sequence);
+    assert_eq!(stderr, expected_error_sequence);
+}
@@ -0,0 +1,52 @@
+mod common;
+use common::run_with_args;
+
+#[test]
+fn test_exit_err_and_return_traps() {
+    let commands = r#"trap 'echo cleanup $?' EXIT; trap 'echo failed $?' ERR
+false; if false; then true; fi; false && true
+f() { trap 'echo returning' RETURN; return 3; }; f; echo status $?
+( trap 'echo subshell exit' EXIT; echo in subshell )
+trap -p EXIT ERR; exit 4"#;
+    let (status, stdout, stderr) = run_with_args(&["-c", commands], "");
+    assert_eq!(status, 4);
+    assert_eq!(
+        stdout,
+        vec![
+            "failed 1",
+            "returning",
+            "failed 3",
+            "status 3",
+            "in subshell",
+            "subshell exit",
+            "trap -- 'echo cleanup $?' EXIT",
+            "trap -- 'echo failed $?' ERR",
+            "cleanup 4",
+        ]
+    );
+    assert!(stderr.is_empty());
+
+    // `exit` in the trap on EXIT changes the status
+    let (status, _, _) = run_with_args(&["-c", "trap 'exit 7' EXIT; echo"], "");
+    assert_eq!(status, 7);
+}
+
+#[test]
+fn test_signal_traps() {
+    let commands = r#"trap 'echo got USR1 $?' USR1; kill -USR1 $$; echo after $?
+trap - USR1; trap '' USR2; trap -p USR1 USR2
+sh -c 'kill -USR2 $$; echo child survived'
+trap 'echo' BOGUS"#;
+    let (status, stdout, stderr) = run_with_args(&["-c", commands], "");
+    assert_eq!(status, 1);
+    assert_eq!(
+        stdout,
+        vec![
+            "got USR1 0",
+            "after 0",
+            "trap -- '' SIGUSR2",
+            "child survived"
+        ]
+    );
+    assert_eq!(stderr, vec!["trap: BOGUS: invalid signal specification"]);
+}
@@ -0,0 +1,31 @@
+mod common;
+use common::run_shell;
+
+#[test]
+fn test_variables_and_export() {
+    let input_sequence: Vec<&str> = vec![
+        "x=hello; echo $x ${x}",
+        // Assignments before a command are only seen by that command
+        r#"y=1 sh -c 'echo child $y'; echo "parent <$y>""#,
+        "export z=exported; sh -c 'echo $z'",
+        r#"w=plain; sh -c 'echo "<$w>"'; export w; sh -c 'echo "<$w>"'"#,
+        r#"unset x; echo "<$x>""#,
+        "export 1bad",
+    ];
+
+    let expected_output_sequence: Vec<&str> = vec![
+        "hello hello",
+        "child 1",
+        "parent <>",
+        "exported",
+        "<>",
+        "<plain>",
+        "<>",
+    ];
+
+    let expected_error_sequence: Vec<&str> = vec!["export: `1bad': not a valid identifier"];
+
+    let (stdout, stderr) = run_shell(&input_sequence);
+    assert_eq!(stdout, expected_output_sequence);
+    assert_eq!(stderr, expected_error_sequence);
+}
@@ -0,0 +1,46 @@
+mod common;
+use common::run_with_args;
+
+#[test]
+fn test_wait_for_jobs() {
+    let commands = r#"sh -c 'exit 3' & p=$!; wait $p; echo pid $?; wait $p; echo again $?
+(exit 4) & sleep 1 & wait -n; echo any $?; wait %2; echo job $?
+sleep 0.2 & jobs; jobs -p | wc -l; wait; echo all $?; jobs
+wait 1; echo missing $?; wait %7; echo $?; wait -n; echo none $?"#;
+    let (status, stdout, stderr) = run_with_args(&["-c", commands], "");
+    assert_eq!(status, 0);
+    assert_eq!(
+        stdout,
+        vec![
+            "pid 3",
+            "again 3",
+            "any 4",
+            "job 0",
+            "[1]+  Running                 sleep 0.2 &",
+            "1",
+            "all 0",
+            "missing 127",
+            "127",
+            "none 127",
+        ]
+    );
+    assert_eq!(
+        stderr,
+        vec![
+            "wait: pid 1 is not a child of this shell",
+            "wait: %7: no such job"
+        ]
+    );
+}
+
+#[test]
+fn test_wait_interrupted_by_trap() {
+    // The trap runs once `wait` returns 128 + the number of the signal
+    let commands = r#"trap 'echo got USR1' USR1
+sh -c "sleep 0.2; kill -USR1 $$" & sleep 5 & wait $!; echo waited $?
+kill -TERM -- -$!; wait $!; echo killed $?"#;
+    let (status, stdout, stderr) = run_with_args(&["-c", commands], "");
+    assert_eq!(status, 0);
+    assert_eq!(stdout, vec!["got USR1", "waited 138", "killed 143"]);
+    assert!(stderr.is_empty());
+}
@@ -0,0 +1,64 @@
+mod common;
+use common::run_with_args;
+
+#[test]
+fn test_trace_with_ps4() {
+    let commands = r#"set -x; x=1 y="a b"; echo "a b" '' "it's" '*' a=b
+for i in 1 2; do true; done; f() { echo in f; }; f
+PS4='$x> '; (echo nested); set +x; echo untraced"#;
+    let (status, stdout, stderr) = run_with_args(&["-c", commands], "");
+    assert_eq!(status, 0);
+    assert_eq!(
+        stdout,
+        vec!["a b  it's * a=b", "in f", "nested", "untraced"]
+    );
+    assert_eq!(
+        stderr,
+        vec![
+            "+ x=1",
+            "+ y='a b'",
+            r"+ echo 'a b' '' 'it'\''s' '*' a=b",
+            "+ for i in 1 2",
+            "+ true",
+            "+ for i in 1 2",
+            "+ true",
+            "+ f",
+            "+ echo in f",
+            "+ PS4='$x> '",
+            // A subshell traces its commands like the shell itself
+            "1> echo nested",
+            "1> set +x",
+        ]
+    );
+
+    let (status, stdout, stderr) = run_with_args(&["-c", "set -x; (echo sub)"], "");
+    assert_eq!(status, 0);
+    assert_eq!(stdout, vec!["sub"]);
+    assert_eq!(stderr, vec!["+ echo sub"]);
+}
+
+#[test]
+fn test_trace_file_descriptor() {
+    let commands = "set -x; BASH_XTRACEFD=1; echo to stdout; BASH_XTRACEFD=9; echo a; echo b";
+    let (status, stdout, stderr) = run_with_args(&["-c", commands], "");
+    assert_eq!(status, 0);
+    assert_eq!(
+        stdout,
+        vec![
+            "+ echo to stdout",
+            "to stdout",
+            "+ BASH_XTRACEFD=9",
+            "a",
+            "b"
+        ]
+    );
+    assert_eq!(
+        stderr,
+        vec![
+            "+ BASH_XTRACEFD=1",
+            "BASH_XTRACEFD: 9: invalid value for trace file descriptor",
+            "+ echo a",
+            "+ echo b",
+        ]
+    );
+}